@@ -1,9 +1,14 @@
 use std::io::prelude::*;
 use std::fs::File;
 use std::env;
+#[cfg(feature = "common")]
+use std::path::Path;
 
 use cxx_build;
 
+const TERSER_VERSION: &str = "5.19.2";
+const SOURCE_MAP_VERSION: &str = "0.3.5";
+
 macro_rules! cargo {
     ($expression:expr$(,)?) => {
         println!("cargo:{}", $expression)
@@ -16,17 +21,40 @@ macro_rules! cargo {
 #[tokio::main]
 async fn main() {
     // TypeScript
-    #[cfg(feature = "transpile")]
+    #[cfg(any(feature = "transpile", feature = "compile"))]
     download_file(&format!("https://unpkg.com/typescript@{}/lib/typescript.js", env::var_os("CARGO_PKG_VERSION").unwrap().to_string_lossy()), &"src/features/transpile/typescript.js").await;
 
     // Terser
     #[cfg(feature = "minify")]
-    download_file(&format!("https://unpkg.com/terser@{}/dist/bundle.min.js", "5.19.2"), &"src/features/minify/terser.js").await;
+    download_file(&format!("https://unpkg.com/terser@{}/dist/bundle.min.js", TERSER_VERSION), &"src/features/minify/terser.js").await;
+
+    // Source map support for Terser
+    #[cfg(feature = "minify")]
+    download_file(&format!("https://unpkg.com/@jridgewell/source-map@{}/dist/source-map.umd.js", SOURCE_MAP_VERSION), &"src/features/minify/source-map.js").await;
+
+    // TypeScript lib files for type checking
+    #[cfg(feature = "compile")]
+    bundle_lib_files(&env::var_os("CARGO_PKG_VERSION").unwrap().to_string_lossy(), &"src/features/compile/lib.js").await;
 
     // Wave
     #[cfg(feature = "preprocess")]
     compile_wave();
 
+    // Startup snapshot with the scripts above already evaluated
+    #[cfg(feature = "common")]
+    create_snapshot(&Path::new(&env::var_os("OUT_DIR").unwrap()).join("snapshot.bin"));
+
+    // Library versions, which are part of compile cache keys (See src/cache.rs)
+    println!("cargo:rustc-env=MTSC_TYPESCRIPT_VERSION={}", env::var_os("CARGO_PKG_VERSION").unwrap().to_string_lossy());
+    println!("cargo:rustc-env=MTSC_TERSER_VERSION={}", TERSER_VERSION);
+    println!("cargo:rustc-env=MTSC_SOURCE_MAP_VERSION={}", SOURCE_MAP_VERSION);
+
+    // Cache keys also hash options through derived Hash implementations, which may change between compilers
+    let rustc_version = std::process::Command::new(env::var_os("RUSTC").unwrap_or_else(|| "rustc".into())).arg("--version").output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_default();
+    println!("cargo:rustc-env=MTSC_RUSTC_VERSION={}", rustc_version);
+
     #[cfg(any(feature = "transpile", feature = "compile"))]
     cargo!("rerun-if-env-changed", "CARGO_PKG_VERSION");
     
@@ -36,6 +64,53 @@ async fn main() {
     cargo!("rerun-if-changed", "build.rs");
 }
 
+#[cfg(feature = "common")]
+fn js_string(text: &str) -> String {
+    let mut result = String::from("\"");
+    for char in text.chars() {
+        match char {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\u{2028}' => result.push_str("\\u2028"),
+            '\u{2029}' => result.push_str("\\u2029"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c)
+        }
+    }
+    result.push('"');
+    return result;
+}
+
+// Downloads every lib.*.d.ts file into a script defining globalThis.mtscLibFiles (see src/features/compile/compile.js)
+#[cfg(feature = "compile")]
+async fn bundle_lib_files(version: &str, path: &str) {
+    let url = format!("https://unpkg.com/typescript@{}/lib/?meta", version);
+    let response = reqwest::get(&url).await.expect(format!("Failed to download {}", url).as_str());
+    let meta = response.text().await.expect(format!("Failed to download {}", url).as_str());
+
+    // The listing is JSON, but only the lib paths are needed
+    let mut names: Vec<&str> = meta.split("\"/lib/").skip(1)
+        .filter_map(|s| s.split('"').next())
+        .filter(|name| name.starts_with("lib.") && name.ends_with(".d.ts"))
+        .collect();
+    names.sort();
+    names.dedup();
+
+    let mut script = String::from("globalThis.mtscLibFiles = {\n");
+    for name in names {
+        let url = format!("https://unpkg.com/typescript@{}/lib/{}", version, name);
+        let response = reqwest::get(&url).await.expect(format!("Failed to download {}", url).as_str());
+        let content = response.text().await.expect(format!("Failed to download {}", url).as_str());
+        script.push_str(&format!("    {}: {},\n", js_string(name), js_string(&content)));
+    }
+    script.push_str("};\n");
+
+    let mut file = File::create(path).expect(format!("Failed to save lib files to {}", path).as_str());
+    file.write_all(script.as_bytes()).expect(format!("Failed to save lib files to {}", path).as_str());
+}
+
 #[cfg(feature = "common")]
 async fn download_file(url: &str, path: &str) {
     let response = reqwest::get(url).await.expect(format!("Failed to download {}", url).as_str());
@@ -45,6 +120,71 @@ async fn download_file(url: &str, path: &str) {
     file.write_all(content.as_bytes()).expect(format!("Failed to save {} to {}", url, path).as_str());
 }
 
+#[cfg(feature = "common")]
+fn run_script<'s>(scope: &mut v8::HandleScope<'s>, text: &str) -> Option<v8::Local<'s, v8::Value>> {
+    let text = v8::String::new(scope, text)?;
+    return v8::Script::compile(scope, text, None)?.run(scope);
+}
+
+// Creates a V8 startup snapshot with TypeScript and Terser already evaluated so that runtimes do not need to evaluate them again
+// An empty file is written if a snapshot cannot be made, in which case the scripts are evaluated at run time (See Runtime::new)
+// The scripts here must match SNAPSHOT_SCRIPTS in src/features/common.rs
+#[cfg(feature = "common")]
+fn create_snapshot(path: &Path) {
+    #[allow(unused_mut)]
+    let mut scripts: Vec<&str> = vec![];
+    #[cfg(any(feature = "transpile", feature = "compile"))]
+    scripts.extend(["src/features/transpile/typescript.js", "src/features/ts-diagnostics.js"]);
+    #[cfg(feature = "minify")]
+    scripts.extend(["src/features/minify/source-map.js", "src/features/minify/terser.js", "src/features/minify/comments.js"]);
+
+    for script in scripts.iter() {
+        cargo!("rerun-if-changed", script);
+    }
+
+    // A snapshot only works with the V8 build that made it, which is not the one that runs when cross compiling
+    let blob: Option<Vec<u8>> = if env::var("HOST") == env::var("TARGET") {
+        let platform = v8::new_default_platform(0, false).make_shared();
+        v8::V8::initialize_platform(platform);
+        v8::V8::initialize();
+
+        let mut isolate = v8::Isolate::snapshot_creator(None, None);
+        let loaded = {
+            let handle_scope = &mut v8::HandleScope::new(&mut isolate);
+            let context = v8::Context::new(handle_scope);
+            let loaded = {
+                let scope = &mut v8::ContextScope::new(handle_scope, context);
+                scripts.iter().all(|script| {
+                    let text = std::fs::read_to_string(script).expect(format!("Failed to read {}", script).as_str());
+                    run_script(scope, &text).is_some()
+                })
+            };
+            handle_scope.set_default_context(context);
+            loaded
+        };
+
+        isolate.create_blob(v8::FunctionCodeHandling::Keep).filter(|_| loaded).map(|blob| blob.to_vec())
+    } else {
+        None
+    };
+
+    if blob.is_none() {
+        cargo!("warning", "Failed to create a V8 startup snapshot, scripts will be evaluated at run time");
+    }
+
+    // The names include_script looks scripts up by, so that which scripts are in the snapshot is only decided here (See SNAPSHOT_SCRIPTS in src/features/common.rs)
+    let names: Vec<String> = if blob.is_some() {
+        scripts.iter().map(|script| format!("{:?}", script.rsplit('/').next().unwrap_or(*script))).collect()
+    } else {
+        vec![]
+    };
+    let names_path = path.with_file_name("snapshot_scripts.rs");
+    std::fs::write(&names_path, format!("&[{}]", names.join(", "))).expect(format!("Failed to save snapshot script names to {}", names_path.display()).as_str());
+
+    let mut file = File::create(path).expect(format!("Failed to save snapshot to {}", path.display()).as_str());
+    file.write_all(&blob.unwrap_or_default()).expect(format!("Failed to save snapshot to {}", path.display()).as_str());
+}
+
 #[cfg(feature = "preprocess")]
 fn compile_wave() {
     cxx_build::bridge("src/features/preprocess/wave.rs")
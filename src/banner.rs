@@ -0,0 +1,160 @@
+// Banners and Footers
+// Options::banner and Options::footer are added to compiled code after every other stage so that minification cannot strip or move them
+// Either one can be literal text or '@' followed by the path of a file to read it from, and these placeholders are replaced:
+//
+//     [file]     The file name of the input without its directory
+//     [path]     The file name of the input as given
+//     [version]  The version of mtsc
+//     [date]     The current date as YYYY-MM-DD in UTC, or the date of SOURCE_DATE_EPOCH when it is set for reproducible builds
+use std::path::Path;
+use std::time::{SystemTime,UNIX_EPOCH};
+
+use crate::Options;
+use crate::error::Error;
+use crate::sourcemap::SourceMap;
+
+// The file a banner or footer is read from, if any
+fn file(text: &Option<String>) -> Option<&str> {
+    return text.as_deref().and_then(|text| text.strip_prefix('@'));
+}
+
+// Files read for the banner and footer, so that they can be watched and checked by the cache like included files
+pub(crate) fn dependencies(options: &Options) -> Vec<String> {
+    return [&options.banner, &options.footer].iter().filter_map(|text| file(text)).map(String::from).collect();
+}
+
+// Whether the banner or footer could change without the input, options or any dependency changing
+pub(crate) fn is_dated(options: &Options) -> bool {
+    return [&options.banner, &options.footer].iter().filter_map(|text| text.as_deref()).any(|text| match text.strip_prefix('@') {
+        // The file itself is a dependency, so only a placeholder in it makes a difference
+        Some(path) => std::fs::read_to_string(path).map_or(false, |text| text.contains("[date]")),
+        None => text.contains("[date]")
+    });
+}
+
+// Converts days since 1970-01-01 to a year, month and day (See http://howardhinnant.github.io/date_algorithms.html#civil_from_days)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month + 2) / 5 + 1) as u32;
+    let month = (if month < 10 {month + 3} else {month - 9}) as u32;
+    let year = year_of_era + era * 400 + if month <= 2 {1} else {0};
+    return (year, month, day);
+}
+
+// Seconds since 1970-01-01 from SOURCE_DATE_EPOCH, or the current time when it is not set
+fn epoch() -> i64 {
+    return match std::env::var("SOURCE_DATE_EPOCH").ok().and_then(|epoch| epoch.trim().parse::<i64>().ok()) {
+        Some(seconds) => seconds,
+        None => SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs() as i64).unwrap_or(0)
+    };
+}
+
+fn date(seconds: i64) -> String {
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+    return format!("{:04}-{:02}-{:02}", year, month, day);
+}
+
+// Reads a banner or footer and replaces its placeholders
+fn expand(text: &str, options: &Options) -> Result<String, Error> {
+    let text = match text.strip_prefix('@') {
+        Some(path) => std::fs::read_to_string(path).map_err(|e| Error::io(Some(path), e))?,
+        None => String::from(text)
+    };
+
+    let path = options.filename.as_deref().unwrap_or("");
+    let file = Path::new(path).file_name().map(|file| file.to_string_lossy().into_owned()).unwrap_or_default();
+
+    let mut text = text.replace("[file]", &file).replace("[path]", path).replace("[version]", env!("CARGO_PKG_VERSION"));
+    if text.contains("[date]") {
+        text = text.replace("[date]", &date(epoch()));
+    }
+    return Ok(text);
+}
+
+// Adds the banner and footer to code, shifting its source map down by the lines the banner adds
+// A banner goes after a hashbang since that has to stay on the first line
+pub(crate) fn apply(mut code: String, source_map: Option<&mut SourceMap>, options: &Options) -> Result<String, Error> {
+    if let Some(ref banner) = options.banner {
+        let mut banner = expand(banner, options)?;
+        if !banner.ends_with('\n') {
+            banner.push('\n');
+        }
+
+        let (index, line) = if code.starts_with("#!") {
+            if !code.contains('\n') {
+                code.push('\n');
+            }
+            (code.find('\n').unwrap() + 1, 1)
+        } else {
+            (0, 0)
+        };
+        code.insert_str(index, &banner);
+
+        if let Some(source_map) = source_map {
+            let lines = banner.matches('\n').count();
+            let line = line.min(source_map.mappings.len());
+            source_map.mappings.splice(line..line, (0..lines).map(|_| vec![]));
+        }
+    }
+
+    if let Some(ref footer) = options.footer {
+        let footer = expand(footer, options)?;
+        if !code.is_empty() && !code.ends_with('\n') {
+            code.push('\n');
+        }
+        code.push_str(&footer);
+        if !code.ends_with('\n') {
+            code.push('\n');
+        }
+    }
+
+    return Ok(code);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(11017), (2000, 3, 1));
+        // Leap days, including 2400 which is divisible by 400
+        assert_eq!(civil_from_days(19782), (2024, 2, 29));
+        assert_eq!(civil_from_days(157113), (2400, 2, 29));
+        // 2100 is not a leap year
+        assert_eq!(civil_from_days(47540), (2100, 2, 28));
+        assert_eq!(civil_from_days(47541), (2100, 3, 1));
+    }
+
+    #[test]
+    fn dates_from_seconds() {
+        assert_eq!(date(0), "1970-01-01");
+        assert_eq!(date(-1), "1969-12-31");
+        assert_eq!(date(1709251199), "2024-02-29");
+        assert_eq!(date(4107542400), "2100-03-01");
+    }
+
+    #[test]
+    fn only_date_placeholders_are_dated() {
+        let banner = std::env::temp_dir().join(format!("mtsc-test-banner-{}.txt", std::process::id()));
+        let options = |banner: &str| Options { banner: Some(String::from(banner)), ..Default::default() };
+
+        std::fs::write(&banner, "/* [file] */").unwrap();
+        let undated = is_dated(&options(&format!("@{}", banner.display())));
+        std::fs::write(&banner, "/* [date] */").unwrap();
+        let dated = is_dated(&options(&format!("@{}", banner.display())));
+        std::fs::remove_file(&banner).unwrap();
+
+        assert!(!undated);
+        assert!(dated);
+        assert!(is_dated(&options("/* built [date] */")));
+        assert!(!is_dated(&options("/* [version] */")));
+    }
+}
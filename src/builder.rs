@@ -0,0 +1,356 @@
+// Options Builder
+// Builds Options without knowing which fields exist under which features
+// Turning something on that needs a feature which is compiled out is an error, while turning it off is a no-op
+use std::path::Path;
+
+use crate::{Options,SourceMapMode};
+use crate::diagnostic::DiagnosticSink;
+use crate::error::{Error,Stage,Location};
+use crate::util::{self,OptionSource};
+
+#[derive(Clone,Default,Debug)]
+pub struct OptionsBuilder {
+    options: Options,
+    // The first error, which is returned by build
+    error: Option<Error>,
+}
+
+// Sets options that need a feature, or records an error if the feature is compiled out and enabled is true
+macro_rules! with_feature {
+    (#[cfg($meta:meta)] $self:ident, $name:literal, $enabled:expr, $body:block) => {
+        {
+            #[cfg($meta)]
+            {
+                $body
+                return $self;
+            }
+            #[cfg(not($meta))]
+            return $self.requires($name, $enabled);
+        }
+    };
+}
+
+// Parameters go unused when their feature is compiled out
+#[allow(unused_variables,unused_mut)]
+impl OptionsBuilder {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    // Starts from existing options
+    pub fn from_options(options: Options) -> Self {
+        return OptionsBuilder { options, error: None };
+    }
+
+    fn error<S: Into<String>>(mut self, message: S) -> Self {
+        if self.error.is_none() {
+            self.error = Some(Error::new(Stage::Config, Location::file(self.options.filename.clone()), message));
+        }
+        return self;
+    }
+
+    fn requires(self, feature: &str, enabled: bool) -> Self {
+        if enabled {
+            return self.error(format!("the {} feature is not enabled", feature));
+        }
+        return self;
+    }
+
+    pub fn filename<S: Into<String>>(mut self, filename: S) -> Self {
+        self.options.filename = Some(filename.into());
+        return self;
+    }
+
+    pub fn diagnostic_sink(mut self, diagnostic_sink: DiagnosticSink) -> Self {
+        self.options.diagnostic_sink = Some(diagnostic_sink);
+        return self;
+    }
+
+    pub fn source_map(mut self, source_map: SourceMapMode) -> Self {
+        self.options.source_map = source_map;
+        return self;
+    }
+
+    // Sets the JavaScript version of compiled code like 'es2022' or 'esnext'
+    pub fn target<S: Into<String>>(mut self, target: S) -> Self {
+        with_feature!(#[cfg(feature = "common")] self, "common", true, {
+            self.options.target = target.into().to_ascii_lowercase();
+        })
+    }
+
+    pub fn module(mut self, module: bool) -> Self {
+        with_feature!(#[cfg(feature = "common")] self, "common", module, {
+            self.options.module = module;
+        })
+    }
+
+    // Sets the module format of compiled code like 'esnext' or 'commonjs' (See ModuleKind)
+    pub fn module_kind<S: AsRef<str>>(mut self, module_kind: S) -> Self {
+        with_feature!(#[cfg(feature = "common")] self, "common", true, {
+            match module_kind.as_ref().parse() {
+                Ok(module_kind) => self.options.module_kind = module_kind,
+                Err(message) => return self.error(message)
+            }
+        })
+    }
+
+    // Transpiles TypeScript without type checking
+    pub fn transpile(mut self, transpile: bool) -> Self {
+        with_feature!(#[cfg(feature = "transpile")] self, "transpile", transpile, {
+            #[cfg(feature = "compile")]
+            {
+                self.options.ts = if transpile {crate::TSMode::Transpile} else {crate::TSMode::Preserve};
+            }
+            #[cfg(not(feature = "compile"))]
+            {
+                self.options.transpile = transpile;
+            }
+        })
+    }
+
+    // Type checks TypeScript before compiling it
+    pub fn type_check(mut self, type_check: bool) -> Self {
+        with_feature!(#[cfg(feature = "compile")] self, "compile", type_check, {
+            #[cfg(feature = "transpile")]
+            {
+                if type_check {
+                    self.options.ts = crate::TSMode::Compile;
+                } else if self.options.ts == crate::TSMode::Compile {
+                    self.options.ts = crate::TSMode::Transpile;
+                }
+            }
+            #[cfg(not(feature = "transpile"))]
+            {
+                self.options.compile = type_check;
+            }
+        })
+    }
+
+    pub fn fail_on_error(mut self, fail_on_error: bool) -> Self {
+        with_feature!(#[cfg(any(feature = "transpile", feature = "compile"))] self, "transpile or compile", fail_on_error, {
+            self.options.fail_on_error = fail_on_error;
+        })
+    }
+
+    pub fn declaration(mut self, declaration: bool) -> Self {
+        with_feature!(#[cfg(any(feature = "transpile", feature = "compile"))] self, "transpile or compile", declaration, {
+            self.options.declaration = declaration;
+        })
+    }
+
+    // Compiles JSX with a factory like 'h' or 'React.createElement'
+    pub fn jsx<S: Into<String>>(mut self, jsx_factory: S) -> Self {
+        with_feature!(#[cfg(any(feature = "transpile", feature = "compile"))] self, "transpile or compile", true, {
+            self.options.use_jsx = true;
+            self.options.jsx_factory = Some(jsx_factory.into());
+            if self.options.jsx_mode == Some(crate::JsxMode::Preserve) {
+                self.options.jsx_mode = Some(crate::JsxMode::Classic);
+            }
+        })
+    }
+
+    pub fn jsx_fragment<S: Into<String>>(mut self, jsx_fragment: S) -> Self {
+        with_feature!(#[cfg(any(feature = "transpile", feature = "compile"))] self, "transpile or compile", true, {
+            self.options.jsx_fragment = Some(jsx_fragment.into());
+        })
+    }
+
+    // Sets how JSX is compiled like 'preserve', 'classic', or 'automatic' (See JsxMode)
+    pub fn jsx_mode<S: AsRef<str>>(mut self, jsx_mode: S) -> Self {
+        with_feature!(#[cfg(any(feature = "transpile", feature = "compile"))] self, "transpile or compile", true, {
+            match jsx_mode.as_ref().parse() {
+                Ok(jsx_mode) => {
+                    self.options.use_jsx = true;
+                    self.options.jsx_mode = Some(jsx_mode);
+                },
+                Err(message) => return self.error(message)
+            }
+        })
+    }
+
+    pub fn jsx_import_source<S: Into<String>>(mut self, jsx_import_source: S) -> Self {
+        with_feature!(#[cfg(any(feature = "transpile", feature = "compile"))] self, "transpile or compile", true, {
+            self.options.jsx_import_source = Some(jsx_import_source.into());
+        })
+    }
+
+    pub fn minify(mut self, minify: bool) -> Self {
+        with_feature!(#[cfg(feature = "minify")] self, "minify", minify, {
+            self.options.minify = minify;
+        })
+    }
+
+    pub fn html(mut self, html: bool) -> Self {
+        with_feature!(#[cfg(feature = "html")] self, "html", html, {
+            self.options.html = html;
+        })
+    }
+
+    pub fn preprocess(mut self, preprocess: bool) -> Self {
+        with_feature!(#[cfg(feature = "preprocess")] self, "preprocess", preprocess, {
+            self.options.preprocess = preprocess;
+        })
+    }
+
+    // Defines a preprocessor macro like 'DEBUG' or 'VERSION=2'
+    pub fn define<S: Into<String>>(mut self, define: S) -> Self {
+        with_feature!(#[cfg(feature = "preprocess")] self, "preprocess", true, {
+            self.options.macros.push(define.into());
+        })
+    }
+
+    pub fn include_path<S: Into<String>>(mut self, include_path: S) -> Self {
+        with_feature!(#[cfg(feature = "preprocess")] self, "preprocess", true, {
+            self.options.include_paths.push(include_path.into());
+        })
+    }
+
+    // Adds text to the start of compiled code after minification, or reads it from a file if it starts with '@' (e.g. '@LICENSE_HEADER.txt')
+    pub fn banner<S: Into<String>>(mut self, banner: S) -> Self {
+        self.options.banner = Some(banner.into());
+        return self;
+    }
+
+    pub fn footer<S: Into<String>>(mut self, footer: S) -> Self {
+        self.options.footer = Some(footer.into());
+        return self;
+    }
+
+    // Enables whatever the file extension calls for like the command line does (e.g. TypeScript for '.ts' or preprocessing for '.p.js')
+    // Also sets the file name if none was set
+    pub fn infer_from_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        let path = path.as_ref();
+        if self.options.filename.is_none() {
+            self.options.filename = Some(path.to_string_lossy().into_owned());
+        }
+
+        util::update_options(OptionSource::Path(path.to_path_buf()), &mut self.options, &util::all_options());
+        return self;
+    }
+
+    // Enables whatever a MIME type like 'text/typescript' calls for
+    pub fn infer_from_mime<S: Into<String>>(mut self, mime: S) -> Self {
+        util::update_options(OptionSource::Mime(mime.into()), &mut self.options, &util::all_options());
+        return self;
+    }
+
+    // Checks for options that do not make sense together
+    fn validate(&self) -> Result<(), String> {
+        #[cfg(any(feature = "transpile", feature = "compile"))]
+        {
+            let options = &self.options;
+            let is_automatic = matches!(options.resolved_jsx_mode(), crate::JsxMode::Automatic | crate::JsxMode::AutomaticDev);
+
+            if options.jsx_fragment.is_some() && options.jsx_factory.is_none() {
+                return Err(String::from("a JSX fragment factory requires a JSX factory"));
+            }
+            if options.jsx_factory.is_some() && is_automatic {
+                return Err(String::from("a JSX factory cannot be used with the automatic JSX runtime (use a JSX import source instead)"));
+            }
+            if options.jsx_import_source.is_some() && !is_automatic {
+                return Err(String::from("a JSX import source requires the automatic JSX runtime"));
+            }
+            if options.declaration && !self.uses_typescript() {
+                return Err(String::from("declarations require TypeScript to be transpiled or type checked"));
+            }
+        }
+
+        return Ok(());
+    }
+
+    #[cfg(any(feature = "transpile", feature = "compile"))]
+    fn uses_typescript(&self) -> bool {
+        cfg_if::cfg_if! {
+            if #[cfg(all(feature = "compile", feature = "transpile"))] {
+                return self.options.ts != crate::TSMode::Preserve;
+            } else if #[cfg(feature = "compile")] {
+                return self.options.compile;
+            } else {
+                return self.options.transpile;
+            }
+        }
+    }
+
+    pub fn build(self) -> Result<Options, Error> {
+        let builder = match self.validate() {
+            Ok(()) => self,
+            Err(message) => self.error(message)
+        };
+
+        return match builder.error {
+            Some(error) => Err(error),
+            None => Ok(builder.options)
+        };
+    }
+}
+
+impl Options {
+    pub fn builder() -> OptionsBuilder {
+        return OptionsBuilder::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(result: Result<Options, Error>) -> String {
+        let error = result.unwrap_err();
+        assert_eq!(error.stage(), Stage::Config);
+        return String::from(error.message());
+    }
+
+    #[cfg(any(feature = "transpile", feature = "compile"))]
+    #[test]
+    fn jsx_fragment_without_factory() {
+        assert_eq!(message(Options::builder().jsx_fragment("Fragment").build()), "a JSX fragment factory requires a JSX factory");
+
+        let options = Options::builder().jsx("h").jsx_fragment("Fragment").build().unwrap();
+        assert_eq!(options.resolved_jsx_mode(), crate::JsxMode::Classic);
+    }
+
+    #[cfg(any(feature = "transpile", feature = "compile"))]
+    #[test]
+    fn jsx_factory_with_automatic_runtime() {
+        assert_eq!(message(Options::builder().jsx("h").jsx_mode("automatic").build()), "a JSX factory cannot be used with the automatic JSX runtime (use a JSX import source instead)");
+        assert!(message(Options::builder().jsx_mode("react-jsxdev").jsx("h").build()).starts_with("a JSX factory cannot be used"));
+    }
+
+    #[cfg(any(feature = "transpile", feature = "compile"))]
+    #[test]
+    fn jsx_import_source_without_automatic_runtime() {
+        assert_eq!(message(Options::builder().jsx_import_source("preact").build()), "a JSX import source requires the automatic JSX runtime");
+        assert_eq!(message(Options::builder().jsx_mode("classic").jsx_import_source("preact").build()), "a JSX import source requires the automatic JSX runtime");
+
+        let options = Options::builder().jsx_mode("automatic").jsx_import_source("preact").build().unwrap();
+        assert_eq!(options.jsx_import_source.as_deref(), Some("preact"));
+    }
+
+    #[cfg(feature = "transpile")]
+    #[test]
+    fn declaration_without_typescript() {
+        assert_eq!(message(Options::builder().declaration(true).build()), "declarations require TypeScript to be transpiled or type checked");
+        assert!(Options::builder().transpile(true).declaration(true).build().unwrap().declaration);
+    }
+
+    // Turning on something that is compiled out fails, but turning it off does not
+    #[cfg(not(feature = "compile"))]
+    #[test]
+    fn compiled_out_feature() {
+        assert_eq!(message(Options::builder().type_check(true).build()), "the compile feature is not enabled");
+        assert!(Options::builder().type_check(false).build().is_ok());
+    }
+
+    #[cfg(not(feature = "html"))]
+    #[test]
+    fn compiled_out_html() {
+        assert_eq!(message(Options::builder().html(true).build()), "the html feature is not enabled");
+        assert!(Options::builder().html(false).build().is_ok());
+    }
+
+    #[cfg(feature = "common")]
+    #[test]
+    fn first_error_is_kept() {
+        assert_eq!(message(Options::builder().module_kind("nope").target("es5").module_kind("esm").build()), "unknown module kind 'nope'");
+    }
+}
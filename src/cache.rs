@@ -0,0 +1,164 @@
+// Compile Cache
+// Outputs are stored on disk keyed by a hash of the input, its options and the versions of everything that could change the output
+// Entries record the files that were included or type checked so that changes to any of them are treated as a miss
+use std::fs;
+use std::hash::{Hash,Hasher};
+use std::io;
+use std::path::{Path,PathBuf};
+
+use crate::{Options,Output};
+
+// Versions of the bundled libraries (See build.rs)
+const VERSIONS: &[&str] = &[
+    env!("CARGO_PKG_VERSION"),
+    env!("MTSC_TYPESCRIPT_VERSION"),
+    env!("MTSC_TERSER_VERSION"),
+    env!("MTSC_SOURCE_MAP_VERSION"),
+    env!("MTSC_RUSTC_VERSION"),
+];
+
+// Compiled in features change what options do, so they are part of the key as well
+const FEATURES: &[bool] = &[
+    cfg!(feature = "preprocess"),
+    cfg!(feature = "transpile"),
+    cfg!(feature = "compile"),
+    cfg!(feature = "minify"),
+    cfg!(feature = "html"),
+];
+
+const HEADER: &str = "mtsc-cache 1";
+
+// Outputs that depend on the shared name cache could be stale whenever it changes, so they are never cached
+// The same goes for banners and footers that may contain the date
+#[cfg(feature = "minify")]
+fn is_cacheable(options: &Options) -> bool {
+    return !(options.minify && options.minify_options.name_cache) && !crate::banner::is_dated(options);
+}
+
+#[cfg(not(feature = "minify"))]
+fn is_cacheable(options: &Options) -> bool {
+    return !crate::banner::is_dated(options);
+}
+
+// 64-bit FNV-1a, since keys and file hashes are stored on disk and DefaultHasher may hash differently in another build
+struct StableHasher(u64);
+
+impl StableHasher {
+    fn new() -> Self {
+        return StableHasher(0xcbf29ce484222325);
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        return self.0;
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+fn hash_file(path: &Path) -> Option<u64> {
+    let mut hasher = StableHasher::new();
+    hasher.write(&fs::read(path).ok()?);
+    return Some(hasher.finish());
+}
+
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Cache {
+    directory: PathBuf,
+}
+
+impl Cache {
+    pub fn new<P: Into<PathBuf>>(directory: P) -> Self {
+        return Cache { directory: directory.into() };
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    // Identifies an entry without looking at any included files, which are checked when it is read
+    pub fn key<T: AsRef<str>>(text: T, options: &Options) -> String {
+        let mut hasher = StableHasher::new();
+        VERSIONS.hash(&mut hasher);
+        FEATURES.hash(&mut hasher);
+        text.as_ref().hash(&mut hasher);
+        options.hash(&mut hasher);
+        return format!("{:016x}", hasher.finish());
+    }
+
+    fn path(&self, key: &str, extension: &str) -> PathBuf {
+        return self.directory.join(format!("{}.{}", key, extension));
+    }
+
+    // Reads a stored output, which is a miss if it is missing, unreadable or any file it included has changed
+    pub fn get<T: AsRef<str>>(&self, text: T, options: &Options) -> Option<Output> {
+        if !is_cacheable(options) {
+            return None;
+        }
+
+        let key = Self::key(text, options);
+
+        let entry = fs::read_to_string(self.path(&key, "deps")).ok()?;
+        let mut lines = entry.lines();
+        if lines.next() != Some(HEADER) {
+            return None;
+        }
+
+        let mut dependencies = Vec::new();
+        for line in lines {
+            let (hash, path) = line.split_once(' ')?;
+            if u64::from_str_radix(hash, 16).ok()? != hash_file(Path::new(path))? {
+                return None;
+            }
+            dependencies.push(String::from(path));
+        }
+
+        return Some(Output {
+            code: fs::read_to_string(self.path(&key, "js")).ok()?,
+            source_map: fs::read_to_string(self.path(&key, "js.map")).ok(),
+            declaration: fs::read_to_string(self.path(&key, "d.ts")).ok(),
+            dependencies,
+            licenses: fs::read_to_string(self.path(&key, "LICENSE.txt")).ok(),
+        });
+    }
+
+    // Stores an output; the list of included files is written last so that a partially written entry is never read
+    pub fn put<T: AsRef<str>>(&self, text: T, options: &Options, output: &Output) -> io::Result<()> {
+        if !is_cacheable(options) {
+            return Ok(());
+        }
+
+        let key = Self::key(text, options);
+        fs::create_dir_all(&self.directory)?;
+
+        let mut entry = String::from(HEADER);
+        for dependency in output.dependencies.iter() {
+            match hash_file(Path::new(dependency)) {
+                Some(hash) => entry.push_str(&format!("\n{:016x} {}", hash, dependency)),
+                // A file that cannot be read now could not be checked later either
+                None => return Ok(())
+            }
+        }
+
+        let _ = fs::remove_file(self.path(&key, "deps"));
+        fs::write(self.path(&key, "js"), &output.code)?;
+
+        for (extension, content) in [("js.map", &output.source_map), ("d.ts", &output.declaration), ("LICENSE.txt", &output.licenses)] {
+            match content {
+                Some(content) => fs::write(self.path(&key, extension), content)?,
+                None => match fs::remove_file(self.path(&key, extension)) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+            }
+        }
+
+        return fs::write(self.path(&key, "deps"), entry);
+    }
+}
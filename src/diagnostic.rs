@@ -0,0 +1,112 @@
+// Diagnostics
+use std::fmt;
+use std::sync::Arc;
+
+use crate::error::{Error,Stage,Location};
+
+#[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord,std::hash::Hash)]
+pub enum Severity {
+    Message,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Message => "message",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+#[derive(Clone,Debug,PartialEq,Eq,std::hash::Hash)]
+pub struct Diagnostic {
+    pub stage: Stage,
+    pub severity: Severity,
+    pub code: Option<u32>,
+    pub location: Location,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new<S: Into<String>>(stage: Stage, severity: Severity, location: Location, message: S) -> Self {
+        Diagnostic { stage, severity, code: None, location, message: message.into() }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    pub fn into_error(self) -> Error {
+        match self.code {
+            Some(code) => Error::new(self.stage, self.location, format!("TS{}: {}", code, self.message)),
+            None => Error::new(self.stage, self.location, self.message)
+        }
+    }
+}
+
+// Formats as 'file:line:col: severity: message' or, for TypeScript, 'file:line:col: severity TS1234: message'
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.code {
+            Some(code) => write!(f, "{}: {} TS{}: {}", self.location, self.severity, code, self.message),
+            None => write!(f, "{}: {}: {}", self.location, self.severity, self.message)
+        }
+    }
+}
+
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum Action {
+    Continue,
+    Fail,
+}
+
+// Receives every diagnostic produced while compiling
+// Returning Action::Fail turns any diagnostic into an error; preprocessor and type errors always fail
+#[derive(Clone)]
+pub struct DiagnosticSink(Arc<dyn Fn(&Diagnostic) -> Action + Send + Sync>);
+
+impl DiagnosticSink {
+    pub fn new<F: Fn(&Diagnostic) -> Action + Send + Sync + 'static>(f: F) -> Self {
+        DiagnosticSink(Arc::new(f))
+    }
+
+    pub fn report(&self, diagnostic: &Diagnostic) -> Action {
+        (self.0)(diagnostic)
+    }
+}
+
+impl fmt::Debug for DiagnosticSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("DiagnosticSink")
+    }
+}
+
+// Sinks do not affect output, so they are ignored when hashing Options
+impl std::hash::Hash for DiagnosticSink {
+    fn hash<H: std::hash::Hasher>(&self, _state: &mut H) {}
+}
+
+// Passes diagnostics to the sink and returns the first failure
+// Without a sink, other diagnostics are discarded; printing them is left to the caller (See main.rs)
+pub(crate) fn report<I: IntoIterator<Item = Diagnostic>>(diagnostics: I, sink: Option<&DiagnosticSink>, fail_on_error: bool) -> Result<(), Error> {
+    let mut failure: Option<Diagnostic> = None;
+
+    for diagnostic in diagnostics {
+        let action = match sink {
+            Some(sink) => sink.report(&diagnostic),
+            None => Action::Continue
+        };
+
+        if failure.is_none() && ((fail_on_error && diagnostic.is_error()) || action == Action::Fail) {
+            failure = Some(diagnostic);
+        }
+    }
+
+    return match failure {
+        Some(diagnostic) => Err(diagnostic.into_error()),
+        None => Ok(())
+    };
+}
@@ -0,0 +1,170 @@
+// Error Handling
+use std::fmt;
+
+#[derive(Clone,Copy,Debug,PartialEq,Eq,std::hash::Hash)]
+pub enum Stage {
+    Preprocess,
+    Compile,
+    Transpile,
+    Minify,
+    Html,
+    V8Init,
+    Io,
+    Config,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Stage::Preprocess => "preprocessor",
+            Stage::Compile => "compiler",
+            Stage::Transpile => "transpiler",
+            Stage::Minify => "minifier",
+            Stage::Html => "html",
+            Stage::V8Init => "v8",
+            Stage::Io => "io",
+            Stage::Config => "config",
+        })
+    }
+}
+
+#[derive(Clone,Default,Debug,PartialEq,Eq,std::hash::Hash)]
+pub struct Location {
+    pub filename: Option<String>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+impl Location {
+    pub fn new(filename: Option<String>, line: Option<usize>, column: Option<usize>) -> Self {
+        Location { filename, line, column }
+    }
+
+    pub fn file(filename: Option<String>) -> Self {
+        Location { filename, ..Default::default() }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.filename.as_deref().unwrap_or("<stdin>"))?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+            if let Some(column) = self.column {
+                write!(f, ":{}", column)?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum Error {
+    Preprocess { location: Location, message: String },
+    Compile { location: Location, message: String },
+    Transpile { location: Location, message: String },
+    Minify { location: Location, message: String },
+    Html { location: Location, message: String },
+    V8Init { location: Location, message: String },
+    Io { location: Location, message: String },
+    Config { location: Location, message: String },
+}
+
+impl Error {
+    pub fn new<S: Into<String>>(stage: Stage, location: Location, message: S) -> Self {
+        let message = message.into();
+        match stage {
+            Stage::Preprocess => Error::Preprocess { location, message },
+            Stage::Compile => Error::Compile { location, message },
+            Stage::Transpile => Error::Transpile { location, message },
+            Stage::Minify => Error::Minify { location, message },
+            Stage::Html => Error::Html { location, message },
+            Stage::V8Init => Error::V8Init { location, message },
+            Stage::Io => Error::Io { location, message },
+            Stage::Config => Error::Config { location, message },
+        }
+    }
+
+    pub fn io<S: Into<String>>(filename: Option<S>, error: std::io::Error) -> Self {
+        Error::Io { location: Location::file(filename.map(Into::into)), message: error.to_string() }
+    }
+
+    pub fn stage(&self) -> Stage {
+        match self {
+            Error::Preprocess { .. } => Stage::Preprocess,
+            Error::Compile { .. } => Stage::Compile,
+            Error::Transpile { .. } => Stage::Transpile,
+            Error::Minify { .. } => Stage::Minify,
+            Error::Html { .. } => Stage::Html,
+            Error::V8Init { .. } => Stage::V8Init,
+            Error::Io { .. } => Stage::Io,
+            Error::Config { .. } => Stage::Config,
+        }
+    }
+
+    pub fn location(&self) -> &Location {
+        match self {
+            Error::Preprocess { location, .. }
+                | Error::Compile { location, .. }
+                | Error::Transpile { location, .. }
+                | Error::Minify { location, .. }
+                | Error::Html { location, .. }
+                | Error::V8Init { location, .. }
+                | Error::Io { location, .. }
+                | Error::Config { location, .. } => location
+        }
+    }
+
+    pub fn location_mut(&mut self) -> &mut Location {
+        match self {
+            Error::Preprocess { location, .. }
+                | Error::Compile { location, .. }
+                | Error::Transpile { location, .. }
+                | Error::Minify { location, .. }
+                | Error::Html { location, .. }
+                | Error::V8Init { location, .. }
+                | Error::Io { location, .. }
+                | Error::Config { location, .. } => location
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Error::Preprocess { message, .. }
+                | Error::Compile { message, .. }
+                | Error::Transpile { message, .. }
+                | Error::Minify { message, .. }
+                | Error::Html { message, .. }
+                | Error::V8Init { message, .. }
+                | Error::Io { message, .. }
+                | Error::Config { message, .. } => message
+        }
+    }
+
+    pub fn filename(&self) -> Option<&str> {
+        self.location().filename.as_deref()
+    }
+
+    pub fn line(&self) -> Option<usize> {
+        self.location().line
+    }
+
+    pub fn column(&self) -> Option<usize> {
+        self.location().column
+    }
+}
+
+// Formats as 'file:line:col: stage error: message'
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} error: {}", self.location(), self.stage(), self.message())
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::io(None::<String>, error)
+    }
+}
@@ -2,9 +2,15 @@
 use runtime::Runtime;
 
 use std::cell::RefCell;
+use std::convert::TryFrom;
 use std::sync::LazyLock;
 use os_thread_local::ThreadLocal;
 
+use crate::error::{Error,Stage,Location};
+use crate::sourcemap::SourceMap;
+#[cfg(any(feature = "transpile", feature = "compile"))]
+use crate::diagnostic::{Diagnostic,Severity};
+
 pub(in crate::features) static TLS_RUNTIME: LazyLock<ThreadLocal<RefCell<Runtime>>> = LazyLock::new(|| ThreadLocal::new(|| RefCell::new(Runtime::new())));
 
 pub fn init_v8(primary: bool) {
@@ -12,8 +18,191 @@ pub fn init_v8(primary: bool) {
     with_v8!{ use _ = TLS_RUNTIME; };
 }
 
+pub(in crate::features) fn get_property<'s>(scope: &mut v8::HandleScope<'s>, object: v8::Local<v8::Object>, name: &str) -> Option<v8::Local<'s, v8::Value>> {
+    let key = v8::String::new(scope, name)?.into();
+    return object.get(scope, key).filter(|value| !value.is_null_or_undefined());
+}
+
+pub(in crate::features) fn get_strings(scope: &mut v8::HandleScope, value: v8::Local<v8::Value>) -> Option<Vec<String>> {
+    let array = v8::Local::<v8::Array>::try_from(value).ok()?;
+    return (0..array.length()).map(|i| {
+        Some(array.get_index(scope, i)?.to_rust_string_lossy(scope))
+    }).collect();
+}
+
+// Builds the TypeScript compiler options object shared by the transpile and compile features
+// See https://www.typescriptlang.org/tsconfig/#compilerOptions
+#[cfg(any(feature = "transpile", feature = "compile"))]
+pub(in crate::features) fn compiler_options<'s>(scope: &mut v8::HandleScope<'s>, options: &crate::Options) -> Option<v8::Local<'s, v8::Object>> {
+    let object = v8::Object::new(scope);
+
+    macro_rules! set {
+        ($prop:ident = $value:expr) => {
+            {
+                let prop_name = v8::String::new(scope, stringify!($prop))?.into();
+                let prop_value = $value;
+                object.set(scope, prop_name, prop_value)?;
+            }
+        };
+    }
+
+    macro_rules! string {
+        ($expression:expr) => {
+            v8::String::new(scope, $expression)?.into()
+        };
+    }
+
+    macro_rules! boolean {
+        ($expression:expr) => {
+            v8::Boolean::new(scope, $expression).into()
+        };
+    }
+
+    set!(target = string!(options.target.as_str()));
+    set!(module = string!(options.module_kind.as_str()));
+    set!(sourceMap = boolean!(options.source_map != crate::SourceMapMode::None));
+    set!(declaration = boolean!(options.declaration));
+
+    if options.use_jsx {
+        let jsx_mode = options.resolved_jsx_mode();
+        set!(jsx = string!(jsx_mode.as_str()));
+
+        match jsx_mode {
+            crate::JsxMode::Classic => {
+                if let Some(ref jsx_factory) = options.jsx_factory {
+                    set!(jsxFactory = string!(jsx_factory.as_str()));
+                }
+                if let Some(ref jsx_fragment) = options.jsx_fragment {
+                    set!(jsxFragmentFactory = string!(jsx_fragment.as_str()));
+                }
+            },
+            crate::JsxMode::Automatic | crate::JsxMode::AutomaticDev => {
+                if let Some(ref jsx_import_source) = options.jsx_import_source {
+                    set!(jsxImportSource = string!(jsx_import_source.as_str()));
+                }
+            },
+            crate::JsxMode::Preserve => {}
+        }
+    }
+
+    let emit = &options.emit;
+    if emit.experimental_decorators {
+        set!(experimentalDecorators = boolean!(true));
+    }
+    if emit.emit_decorator_metadata {
+        set!(emitDecoratorMetadata = boolean!(true));
+    }
+    if let Some(use_define_for_class_fields) = emit.use_define_for_class_fields {
+        set!(useDefineForClassFields = boolean!(use_define_for_class_fields));
+    }
+    if emit.import_helpers {
+        set!(importHelpers = boolean!(true));
+    }
+    if emit.es_module_interop {
+        set!(esModuleInterop = boolean!(true));
+    }
+    if emit.downlevel_iteration {
+        set!(downlevelIteration = boolean!(true));
+    }
+    if emit.verbatim_module_syntax {
+        set!(verbatimModuleSyntax = boolean!(true));
+    }
+    if emit.remove_comments {
+        set!(removeComments = boolean!(true));
+    }
+
+    // Transpiling ignores these, so they only matter when type checking
+    #[cfg(feature = "compile")]
+    for (name, value) in options.type_check_options.iter() {
+        let key = v8::String::new(scope, name)?.into();
+        let value = boolean!(*value);
+        object.set(scope, key, value)?;
+    }
+
+    return Some(object);
+}
+
+// TypeScript always links its map as an external file; the final comment (if any) is added once every stage is done
+#[cfg(any(feature = "transpile", feature = "compile"))]
+pub(in crate::features) fn strip_source_mapping_url(text: String) -> String {
+    match text.trim_end().rfind('\n') {
+        Some(i) if text[i+1..].starts_with("//# sourceMappingURL=") => String::from(&text[..i+1]),
+        None if text.starts_with("//# sourceMappingURL=") => String::new(),
+        _ => text
+    }
+}
+
+// Reads diagnostics converted by mtscToDiagnostic (see ts-diagnostics.js)
+#[cfg(any(feature = "transpile", feature = "compile"))]
+pub(in crate::features) fn to_diagnostics(scope: &mut v8::HandleScope, value: v8::Local<v8::Value>, stage: Stage) -> Option<Vec<Diagnostic>> {
+    let array = v8::Local::<v8::Array>::try_from(value).ok()?;
+    return (0..array.length()).map(|i| {
+        let object = array.get_index(scope, i)?.to_object(scope)?;
+
+        let severity = match get_property(scope, object, "category")?.to_rust_string_lossy(scope).as_str() {
+            "Error" => Severity::Error,
+            "Warning" => Severity::Warning,
+            _ => Severity::Message
+        };
+        let code = get_property(scope, object, "code").and_then(|code| code.uint32_value(scope));
+        let message = get_property(scope, object, "message")?.to_rust_string_lossy(scope);
+        let filename = get_property(scope, object, "file").map(|file| file.to_rust_string_lossy(scope));
+        let line = get_property(scope, object, "line").and_then(|line| line.uint32_value(scope)).map(|line| line as usize);
+        let column = get_property(scope, object, "column").and_then(|column| column.uint32_value(scope)).map(|column| column as usize);
+
+        Some(Diagnostic { stage, severity, code, location: Location::new(filename, line, column), message })
+    }).collect();
+}
+
+// Converts a thrown value (or a rejected promise's result) into an Error
+// Parse errors from Terser carry 'line' and 'col' properties which are used when present
+pub(in crate::features) fn to_error(scope: &mut v8::HandleScope, exception: Option<v8::Local<v8::Value>>, stage: Stage, filename: Option<String>) -> Error {
+    let mut location = Location::file(filename);
+
+    let exception = match exception {
+        Some(exception) => exception,
+        None => return Error::new(stage, location, "unknown error in V8")
+    };
+
+    let mut message = exception.to_rust_string_lossy(scope);
+
+    if let Ok(object) = v8::Local::<v8::Object>::try_from(exception) {
+        location.line = get_property(scope, object, "line").and_then(|line| line.integer_value(scope)).map(|line| line as usize);
+        location.column = get_property(scope, object, "col").and_then(|column| column.integer_value(scope)).map(|column| column as usize + 1);
+        
+        if let Some(inner_message) = get_property(scope, object, "message") {
+            message = inner_message.to_rust_string_lossy(scope);
+        }
+    }
+
+    return Error::new(stage, location, message);
+}
+
+// Reads a source map from either its JSON text or an already parsed object
+pub(in crate::features) fn to_source_map(scope: &mut v8::HandleScope, value: v8::Local<v8::Value>) -> Option<SourceMap> {
+    let value = if value.is_string() {
+        let text = value.to_string(scope)?;
+        v8::json::parse(scope, text)?
+    } else {
+        value
+    };
+
+    let object = value.to_object(scope)?;
+
+    let sources = get_property(scope, object, "sources")?;
+    let sources = get_strings(scope, sources)?;
+    let names = match get_property(scope, object, "names") {
+        Some(names) => get_strings(scope, names)?,
+        None => vec![]
+    };
+    let mappings = get_property(scope, object, "mappings")?.to_rust_string_lossy(scope);
+
+    return SourceMap::new(None, sources, names, &mappings);
+}
+
 pub(in crate::features) mod runtime {
     use std::cell::RefCell;
+    use std::collections::HashSet;
     use std::sync::LazyLock;
     use os_thread_local::ThreadLocal;
     use super::once;
@@ -32,18 +221,32 @@ pub(in crate::features) mod runtime {
         });
     }
 
+    // Startup snapshot made by build.rs, which is empty if one could not be made
+    static SNAPSHOT: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/snapshot.bin"));
+
+    // Names of the scripts evaluated in the snapshot, generated by create_snapshot in build.rs (See include_script)
+    const SNAPSHOT_SCRIPTS: &[&str] = include!(concat!(env!("OUT_DIR"), "/snapshot_scripts.rs"));
+
     // Based on https://github.com/abnud1/rust-ssr/blob/main/src/ssr.rs#L51-L57
     pub(in crate::features) struct Runtime {
         pub isolate: v8::OwnedIsolate,
         pub context: v8::Global<v8::Context>,
+        pub loaded_scripts: HashSet<&'static str>,
     }
 
     #[allow(unused)]
     impl Runtime {
         pub fn new() -> Self {
             init_v8(true);
+
+            // Without a snapshot, scripts are evaluated as they are included
+            let (params, loaded_scripts) = if SNAPSHOT.is_empty() {
+                (v8::CreateParams::default(), HashSet::new())
+            } else {
+                (v8::CreateParams::default().snapshot_blob(SNAPSHOT), SNAPSHOT_SCRIPTS.iter().copied().collect())
+            };
             
-            let mut isolate = v8::Isolate::new(Default::default());
+            let mut isolate = v8::Isolate::new(params);
             isolate.add_near_heap_limit_callback(get_new_heap_size, std::ptr::null_mut());
 
             let context = {
@@ -55,6 +258,7 @@ pub(in crate::features) mod runtime {
             return Self {
                 isolate,
                 context,
+                loaded_scripts,
             };
         }
 
@@ -102,6 +306,7 @@ macro_rules! with_v8 {
                 let handle_scope = &mut v8::HandleScope::new(&mut $runtime.isolate);
                 let context = v8::Local::new(handle_scope, &$runtime.context);
                 let scope = &mut v8::ContextScope::new(handle_scope, context);
+                let scope = &mut v8::TryCatch::new(scope);
         
                 #[allow(unused_macros)]
                 macro_rules! scope {
@@ -117,6 +322,16 @@ macro_rules! with_v8 {
                     }
                 }
 
+                #[allow(unused_macros)]
+                macro_rules! exception {
+                    ($$stage:expr, $$filename:expr) => {
+                        {
+                            let exception = scope.exception();
+                            $crate::features::common::to_error(scope, exception, $$stage, $$filename)
+                        }
+                    };
+                }
+
                 #[allow(unused_macros)]
                 macro_rules! v8_str {
                     ($expression:expr) => {
@@ -175,14 +390,24 @@ macro_rules! with_v8 {
 
 pub(in crate::features) use with_v8;
 
+// Evaluates a bundled script once per runtime, evaluating to an Err if it failed to load
+// Scripts are identified by file name so that features sharing a script only load it once
 #[macro_export]
 macro_rules! include_script {
     ($src:expr,$script:literal) => {
         {
             use $crate::features::common::runtime::UsingRuntime as _;
             $src.using_runtime(|runtime| {
-                runtime.run(include_str!($script)).expect(concat!("Error loading ", $script));
-            });
+                let name: &'static str = $script.rsplit('/').next().unwrap_or($script);
+                if runtime.loaded_scripts.contains(name) {
+                    Ok(())
+                } else if runtime.run(include_str!($script)).is_some() {
+                    runtime.loaded_scripts.insert(name);
+                    Ok(())
+                } else {
+                    Err($crate::Error::new($crate::error::Stage::V8Init, Default::default(), concat!("error loading ", $script)))
+                }
+            })
         }
     };
 }
@@ -1,7 +0,0 @@
-compile_error!("feature \"compile\" is not implemented yet");
-
-use crate::Options;
-
-pub fn compile(text: String, options: &Options) -> Option<String> {
-    Some(text)
-}
\ No newline at end of file
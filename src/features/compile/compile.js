@@ -0,0 +1,94 @@
+// Compile Feature
+// Type checks and emits a single file using an in-memory compiler host
+// Diagnostics are converted with mtscToDiagnostic (see ts-diagnostics.js)
+// Bundled lib files (see lib.js) are served from '/lib/'; any other file is read through mtscReadFile (see mod.rs)
+// Imports are resolved on disk through mtscDirectoryExists, mtscGetDirectories, and mtscRealpath so that packages in node_modules are found
+(function() {
+    const LIB_LOCATION = '/lib/';
+    const libSourceFiles = new Map();
+
+    globalThis.mtscCompile = function(text, fileName, jsonCompilerOptions, currentDirectory) {
+        // Options are passed as they are written in a tsconfig.json (See compiler_options in common.rs), but createProgram takes them parsed
+        const { options: compilerOptions, errors } = ts.convertCompilerOptionsFromJson(jsonCompilerOptions, currentDirectory);
+        const sourceFiles = new Map();
+        const outputs = {};
+        // Files read from disk, which can change the output without the input changing
+        const dependencies = [];
+
+        // TypeScript normalizes root names (e.g. './a.ts' or 'src\\a.ts'), so names are compared as normalized absolute paths
+        // Otherwise the input would be read from disk without preprocessing, or not found at all
+        const absolutePath = name => ts.getNormalizedAbsolutePath(ts.normalizePath(name), currentDirectory);
+        const rootFileName = ts.normalizePath(fileName);
+        const rootPath = absolutePath(fileName);
+
+        function readFile(name) {
+            if(absolutePath(name) === rootPath) {
+                return text;
+            } else if(name.startsWith(LIB_LOCATION)) {
+                return mtscLibFiles[name.slice(LIB_LOCATION.length)];
+            } else {
+                const content = mtscReadFile(name);
+                if(content !== undefined && !dependencies.includes(name)) {
+                    dependencies.push(name);
+                }
+                return content;
+            }
+        }
+
+        const host = {
+            getSourceFile(name, languageVersion) {
+                const isLib = name.startsWith(LIB_LOCATION);
+                const cache = isLib ? libSourceFiles : sourceFiles;
+                const key = isLib ? `${languageVersion}:${name}` : name;
+
+                if(!cache.has(key)) {
+                    const content = readFile(name);
+                    cache.set(key, content === undefined ? undefined : ts.createSourceFile(name, content, languageVersion, true));
+                }
+                return cache.get(key);
+            },
+            getDefaultLibFileName: options => LIB_LOCATION + ts.getDefaultLibFileName(options),
+            getDefaultLibLocation: () => LIB_LOCATION,
+            writeFile(name, data) {
+                outputs[name.match(/(\.d)?\.[^.]*$/)[0]] = data;
+            },
+            getCurrentDirectory: () => currentDirectory,
+            getDirectories: name => name.startsWith(LIB_LOCATION) ? [] : mtscGetDirectories(name),
+            directoryExists: name => name.startsWith(LIB_LOCATION) ? name === LIB_LOCATION || name + '/' === LIB_LOCATION : mtscDirectoryExists(name),
+            realpath: name => name.startsWith(LIB_LOCATION) ? name : mtscRealpath(name),
+            getCanonicalFileName: name => name,
+            useCaseSensitiveFileNames: () => true,
+            getNewLine: () => '\n',
+            fileExists: name => readFile(name) !== undefined,
+            readFile,
+        };
+
+        // TypeScript's default for most module kinds is the classic resolution, which never looks in node_modules
+        // Bundler resolution only works with ES modules, so everything else uses node10 like older versions of tsc
+        if(compilerOptions.moduleResolution === undefined) {
+            const module = compilerOptions.module;
+            if(module === ts.ModuleKind.Node16 || module === ts.ModuleKind.NodeNext) {
+                compilerOptions.moduleResolution = ts.ModuleResolutionKind.Node16;
+            } else if(module >= ts.ModuleKind.ES2015 && module <= ts.ModuleKind.ESNext) {
+                compilerOptions.moduleResolution = ts.ModuleResolutionKind.Bundler;
+            } else {
+                compilerOptions.moduleResolution = ts.ModuleResolutionKind.Node10;
+            }
+        }
+
+        const program = ts.createProgram([rootFileName], compilerOptions, host);
+        const sourceFile = program.getSourceFile(rootFileName);
+
+        const diagnostics = [...errors, ...ts.getPreEmitDiagnostics(program, sourceFile)].map(mtscToDiagnostic);
+        const result = program.emit(sourceFile);
+        diagnostics.push(...result.diagnostics.map(mtscToDiagnostic));
+
+        return {
+            outputText: outputs['.js'] ?? outputs['.mjs'] ?? outputs['.cjs'] ?? outputs['.jsx'] ?? '',
+            sourceMapText: outputs['.map'],
+            declarationText: outputs['.d.ts'] ?? outputs['.d.mts'] ?? outputs['.d.cts'],
+            diagnostics,
+            dependencies,
+        };
+    };
+})();
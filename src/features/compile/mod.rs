@@ -0,0 +1,155 @@
+// Compile Feature
+use super::common::{with_v8,include_script,compiler_options,to_source_map,to_diagnostics,get_strings,strip_source_mapping_url,TLS_RUNTIME};
+use super::TSOutput;
+use crate::Options;
+use crate::error::{Error,Stage};
+
+use std::convert::TryFrom;
+use std::path::Path;
+
+// Exposed to compile.js as mtscReadFile so that imported files can be type checked; returns undefined if the file cannot be read
+fn read_file(scope: &mut v8::HandleScope, args: v8::FunctionCallbackArguments, mut return_value: v8::ReturnValue) {
+    let path = args.get(0).to_rust_string_lossy(scope);
+    if let Some(text) = std::fs::read_to_string(path).ok().and_then(|text| v8::String::new(scope, &text)) {
+        return_value.set(text.into());
+    }
+}
+
+// Exposed to compile.js as mtscDirectoryExists, mtscGetDirectories, and mtscRealpath so that imports can be resolved in node_modules
+fn directory_exists(scope: &mut v8::HandleScope, args: v8::FunctionCallbackArguments, mut return_value: v8::ReturnValue) {
+    let path = args.get(0).to_rust_string_lossy(scope);
+    return_value.set(v8::Boolean::new(scope, Path::new(&path).is_dir()).into());
+}
+
+fn get_directories(scope: &mut v8::HandleScope, args: v8::FunctionCallbackArguments, mut return_value: v8::ReturnValue) {
+    let path = args.get(0).to_rust_string_lossy(scope);
+    let names: Vec<String> = std::fs::read_dir(path).map(|entries| {
+        entries.filter_map(Result::ok)
+            .filter(|entry| entry.file_type().map_or(false, |file_type| file_type.is_dir()))
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect()
+    }).unwrap_or_default();
+
+    let names: Option<Vec<v8::Local<v8::Value>>> = names.iter().map(|name| v8::String::new(scope, name).map(Into::into)).collect();
+    if let Some(names) = names {
+        return_value.set(v8::Array::new_with_elements(scope, &names).into());
+    }
+}
+
+// Packages linked into node_modules resolve to where they really are, like they do for tsc; paths that do not exist are returned as is
+fn realpath(scope: &mut v8::HandleScope, args: v8::FunctionCallbackArguments, mut return_value: v8::ReturnValue) {
+    let path = args.get(0).to_rust_string_lossy(scope);
+    let path = std::fs::canonicalize(&path).map(|path| path.to_string_lossy().into_owned()).unwrap_or(path);
+    if let Some(path) = v8::String::new(scope, &path) {
+        return_value.set(path.into());
+    }
+}
+
+pub fn compile(text: String, options: &Options) -> Result<TSOutput, Error> {
+    include_script!(TLS_RUNTIME, r"../transpile/typescript.js")?;
+    include_script!(TLS_RUNTIME, r"../ts-diagnostics.js")?;
+    include_script!(TLS_RUNTIME, r"lib.js")?;
+    include_script!(TLS_RUNTIME, r"compile.js")?;
+
+    return with_v8! {
+        use runtime = TLS_RUNTIME;
+
+        let result: Option<TSOutput> = try {
+            let global_this = global_this!();
+
+            macro_rules! expose {
+                ($($name:ident = $callback:ident),*) => {$(
+                    if v8_get!(global_this.$name).filter(|value| value.is_function()).is_none() {
+                        let callback = v8::Function::new(scope!(), $callback)?;
+                        v8_set!(global_this.$name = callback.into());
+                    }
+                )*};
+            }
+            expose!(mtscReadFile = read_file, mtscDirectoryExists = directory_exists, mtscGetDirectories = get_directories, mtscRealpath = realpath);
+
+            let compile = v8::Local::<v8::Function>::try_from(v8_get!(global_this.mtscCompile)?).ok()?;
+
+            let filename = options.filename.clone().unwrap_or_else(|| String::from(if options.use_jsx {"input.tsx"} else {"input.ts"}));
+            let current_directory = std::env::current_dir().map(|path| path.to_string_lossy().into_owned()).unwrap_or_else(|_| String::from("/"));
+
+            let text = v8_str!(text.as_str());
+            let filename = v8_str!(filename.as_str());
+            let compiler_options = compiler_options(scope!(), options)?;
+            let current_directory = v8_str!(current_directory.as_str());
+
+            let output = compile.call(scope!(), global_this.into(), &[text, filename, compiler_options.into(), current_directory])?.to_object(scope!())?;
+            let output_text = v8_get!(output.outputText)?.to_rust_string_lossy(scope!());
+
+            let source_map = match v8_get!(output.sourceMapText).filter(|value| !value.is_null_or_undefined()) {
+                Some(source_map_text) => Some(to_source_map(scope!(), source_map_text)?),
+                None => None
+            };
+
+            let declaration = match v8_get!(output.declarationText).filter(|value| !value.is_null_or_undefined()) {
+                Some(declaration_text) => Some(declaration_text.to_rust_string_lossy(scope!())),
+                None => None
+            };
+
+            let diagnostics = v8_get!(output.diagnostics)?;
+            let diagnostics = to_diagnostics(scope!(), diagnostics, Stage::Compile)?;
+
+            let dependencies = v8_get!(output.dependencies)?;
+            let dependencies = get_strings(scope!(), dependencies)?;
+
+            TSOutput {
+                code: strip_source_mapping_url(output_text),
+                source_map,
+                declaration,
+                diagnostics,
+                fail_on_error: true,
+                dependencies,
+            }
+        };
+
+        result.ok_or_else(|| exception!(Stage::Compile, options.filename.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // The input does not exist on disk, so it is only found if the host matches TypeScript's normalized name to it
+    #[cfg(feature = "preprocess")]
+    #[test]
+    fn relative_preprocessed_input() {
+        #[allow(unused_mut)]
+        let mut options = crate::Options { filename: Some(String::from("./mtsc-test-input.p.ts")), preprocess: true, ..Default::default() };
+        #[cfg(feature = "transpile")]
+        {
+            options.ts = crate::TSMode::Compile;
+        }
+
+        let code = crate::compile("#define VALUE 2\nexport const value: number = VALUE;\n", &options).unwrap();
+        assert!(code.contains("value = 2"), "{}", code);
+    }
+
+    // Imports are type checked against the files they resolve to, including packages in node_modules
+    #[test]
+    fn resolves_imports() {
+        let directory = std::env::temp_dir().join(format!("mtsc-test-resolution-{}", std::process::id()));
+        let package = directory.join("node_modules").join("pkg");
+        std::fs::create_dir_all(&package).unwrap();
+        std::fs::write(directory.join("sibling.ts"), "export const a: number = 1;\n").unwrap();
+        std::fs::write(package.join("package.json"), "{\"name\": \"pkg\", \"types\": \"index.d.ts\"}\n").unwrap();
+        std::fs::write(package.join("index.d.ts"), "export declare const b: number;\n").unwrap();
+
+        #[allow(unused_mut)]
+        let mut options = crate::Options { filename: Some(directory.join("main.ts").to_string_lossy().into_owned()), ..Default::default() };
+        #[cfg(feature = "transpile")]
+        {
+            options.ts = crate::TSMode::Compile;
+        }
+
+        let (result, dependencies) = crate::compile_output_with_dependencies("import {a} from './sibling';\nimport {b} from 'pkg';\nexport const c: number = a + b;\n", &options);
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let code = result.unwrap().code;
+        assert!(code.contains("a + b"), "{}", code);
+        assert!(dependencies.iter().any(|dependency| dependency.ends_with("sibling.ts")), "{:?}", dependencies);
+        assert!(dependencies.iter().any(|dependency| dependency.ends_with("index.d.ts")), "{:?}", dependencies);
+    }
+}
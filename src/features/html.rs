@@ -6,6 +6,7 @@ use html5ever::tokenizer::{
 };
 
 use crate::Options;
+use crate::error::{Error,Stage,Location};
 
 #[derive(PartialEq)]
 enum TargetType {
@@ -16,7 +17,13 @@ struct Document<'a> {
     options: &'a Options,
     typescript_mode: TargetType,
     inner_html: String,
-    script_buffer: String
+    script_buffer: String,
+    script_line: u64,
+    // License comments extracted from every minified script (See MinifyOptions::extract_licenses)
+    licenses: Vec<String>,
+    // Files read while compiling every script, such as headers that were #included
+    dependencies: Vec<String>,
+    error: Option<Error>
 }
 
 impl<'a> Document<'a> {
@@ -32,7 +39,22 @@ impl<'a> Document<'a> {
             options,
             typescript_mode: TargetType::None,
             inner_html: String::new(),
-            script_buffer: String::new()
+            script_buffer: String::new(),
+            script_line: 1,
+            licenses: vec![],
+            dependencies: vec![],
+            error: None
+        }
+    }
+
+    // Errors within scripts are reported relative to the HTML file rather than the script
+    fn set_error(&mut self, mut error: Error) {
+        if self.error.is_none() {
+            let script_line = self.script_line as usize;
+            let location = error.location_mut();
+            location.filename = self.options.filename.clone();
+            location.line = location.line.map(|line| line + script_line - 1);
+            self.error = Some(error);
         }
     }
 }
@@ -42,7 +64,7 @@ impl<'a> TokenSink for &mut Document<'a> {
     fn adjusted_current_node_present_but_not_in_html_namespace(&self) -> bool {
         true
     }
-    fn process_token(&mut self, token: Token, _line_number: u64) -> TokenSinkResult<()> {
+    fn process_token(&mut self, token: Token, line_number: u64) -> TokenSinkResult<()> {
         match token {
             CharacterTokens(str_tendril) => {
                 self.write_text(str_tendril);
@@ -96,12 +118,14 @@ impl<'a> TokenSink for &mut Document<'a> {
                                     "text/typescript" => {
                                         tag.attrs.retain(|attr| attr.name.local.as_ref() != "type");
                                         self.write_text(get_tag_str(tag));
-                                        self.typescript_mode = TargetType::Classic
+                                        self.typescript_mode = TargetType::Classic;
+                                        self.script_line = line_number;
                                     },
                                     "tsmodule" => {
                                         attr.value = StrTendril::from("module");
                                         self.write_text(get_tag_str(tag));
-                                        self.typescript_mode = TargetType::Module
+                                        self.typescript_mode = TargetType::Module;
+                                        self.script_line = line_number;
                                     },
                                     _ => self.write_text(get_tag_str(tag))
                                 }
@@ -115,6 +139,10 @@ impl<'a> TokenSink for &mut Document<'a> {
                                 let mut options = self.options.clone();
                                 
                                 options.module = self.typescript_mode == TargetType::Module;
+                                // Browsers only load module scripts as ES modules
+                                if options.module {
+                                    options.module_kind = crate::ModuleKind::ESNext;
+                                }
                                 self.typescript_mode = TargetType::None;
 
                                 let script_buffer = self.script_buffer.clone();
@@ -137,17 +165,35 @@ impl<'a> TokenSink for &mut Document<'a> {
                                     }
                                 }
 
-                                let mut text = crate::compile_script(
+                                let text = crate::compile_script_internal(
                                         &script_buffer.lines().map(|line| line.strip_prefix(indentation.as_str()).unwrap_or(line).to_string()).collect::<Vec<String>>().join("\n"),
-                                        &options
-                                    ).expect("error compiling TypeScript within HTML")
-                                    .lines().map(|line| format!("{}{}", indentation, line)).collect::<Vec<String>>().join("\n")
-                                ;
+                                        &options,
+                                        &mut self.dependencies
+                                    ).map(|output| output.code);
 
                                 #[cfg(feature = "minify")]
-                                if options.minify {
-                                    text = super::minify(text,&options).expect("error minifying within HTML").lines().map(|line| format!("{}{}", indentation, line)).collect::<Vec<String>>().join("\n");
-                                }
+                                let text = text.and_then(|text| if options.minify {
+                                    super::minify(text,&options).map(|(text, _, licenses)| {
+                                        for license in licenses {
+                                            if !self.licenses.contains(&license) {
+                                                self.licenses.push(license);
+                                            }
+                                        }
+                                        text
+                                    })
+                                } else {
+                                    Ok(text)
+                                });
+
+                                let text = text.and_then(|text| crate::banner::apply(text, None, &options));
+
+                                let text = match text {
+                                    Ok(text) => text.lines().map(|line| format!("{}{}", indentation, line)).collect::<Vec<String>>().join("\n"),
+                                    Err(error) => {
+                                        self.set_error(error);
+                                        script_buffer.clone()
+                                    }
+                                };
 
                                 self.write_text(format!("\n{}",text));
                                 
@@ -178,7 +224,9 @@ impl<'a> TokenSink for &mut Document<'a> {
     }
 }
 
-pub fn compile_html(text: String, options: &Options) -> Option<String> {
+// Returns the compiled HTML and any license comments extracted from its scripts
+// Files read by the scripts are added to dependencies even if compiling fails so that they can still be watched
+pub fn compile_html(text: String, options: &Options, dependencies: &mut Vec<String>) -> Result<(String, Vec<String>), Error> {
     let mut document = Document::new(options);
     
     let mut input = BufferQueue::new();
@@ -191,5 +239,38 @@ pub fn compile_html(text: String, options: &Options) -> Option<String> {
     let _ = tokenizer.feed(&mut input);
     tokenizer.end();
 
-    return Some(document.inner_html);
-}
\ No newline at end of file
+    if document.typescript_mode != TargetType::None && document.error.is_none() {
+        document.error = Some(Error::new(Stage::Html, Location::new(options.filename.clone(), Some(document.script_line as usize), None), "unterminated script tag"));
+    }
+
+    for dependency in document.dependencies.drain(..) {
+        if !dependencies.contains(&dependency) {
+            dependencies.push(dependency);
+        }
+    }
+
+    return match document.error {
+        Some(error) => Err(error),
+        None => Ok((document.inner_html, document.licenses))
+    };
+}
+#[cfg(test)]
+mod tests {
+    // Headers included by a script are dependencies of the HTML file so that it is rebuilt when they change
+    #[cfg(feature = "preprocess")]
+    #[test]
+    fn script_dependencies() {
+        let header = std::env::temp_dir().join(format!("mtsc-test-html-{}.h", std::process::id()));
+        std::fs::write(&header, "#define VALUE 3\n").unwrap();
+        let header = header.to_string_lossy().into_owned();
+
+        let options = crate::Options { filename: Some(String::from("index.html")), html: true, preprocess: true, ..Default::default() };
+        let html = format!("<script type=\"text/typescript\">\n#include \"{}\"\nconst value = VALUE;\n</script>\n", header);
+        let (result, dependencies) = crate::compile_output_with_dependencies(html, &options);
+        std::fs::remove_file(&header).unwrap();
+
+        let code = result.unwrap().code;
+        assert!(code.contains("const value = 3;"), "{}", code);
+        assert!(dependencies.iter().any(|dependency| dependency.ends_with(&format!("mtsc-test-html-{}.h", std::process::id()))), "{:?}", dependencies);
+    }
+}
@@ -0,0 +1,34 @@
+// Builds Terser's format.comments option from a crate::CommentMode
+// When extracted is an array, kept comments are moved into it instead of the output so that they can be written to a separate file
+globalThis.mtscComments = function(condition, extracted) {
+    // The same comments Terser keeps for 'some'
+    const license = /@preserve|@copyright|@lic|@cc_on|^\**!/i;
+
+    let test;
+    if(condition === 'all') {
+        test = () => true;
+    } else if(condition === 'some') {
+        test = (text) => license.test(text);
+    } else if(condition === 'none') {
+        test = () => false;
+    } else {
+        // Either '/pattern/flags' or just a pattern
+        const match = /^\/(.*)\/([a-z]*)$/s.exec(condition);
+        const regex = match ? new RegExp(match[1], match[2]) : new RegExp(condition);
+        test = (text) => regex.test(text);
+    }
+
+    if(!extracted) {
+        return (node, comment) => test(comment.value);
+    }
+
+    return function(node, comment) {
+        if(test(comment.value)) {
+            const text = comment.type === 'comment2' ? `/*${comment.value}*/` : `//${comment.value}`;
+            if(!extracted.includes(text)) {
+                extracted.push(text);
+            }
+        }
+        return false;
+    };
+};
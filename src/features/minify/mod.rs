@@ -1,9 +1,26 @@
 // Minify Feature
-use super::common::{with_v8,include_script,once_per_thread,TLS_RUNTIME};
+use super::common::{with_v8,include_script,to_source_map,TLS_RUNTIME};
+use super::SourceMapMode;
 use crate::Options;
+use crate::error::{Error,Stage,Location};
+use crate::sourcemap::SourceMap;
 
 use std::convert::TryFrom;
 use std::ops::Deref;
+use std::sync::{Mutex,PoisonError};
+
+// Terser's name cache as JSON, shared by every file minified with MinifyOptions::name_cache in this process
+static NAME_CACHE: Mutex<Option<String>> = Mutex::new(None);
+
+// Replaces the shared name cache, such as with one saved by a previous build
+pub fn set_name_cache(name_cache: Option<String>) {
+    *NAME_CACHE.lock().unwrap_or_else(PoisonError::into_inner) = name_cache;
+}
+
+// The shared name cache as JSON, which is None until a file is minified with it or it is set
+pub fn name_cache() -> Option<String> {
+    return NAME_CACHE.lock().unwrap_or_else(PoisonError::into_inner).clone();
+}
 
 fn format_ecma_version_string<S: Deref<Target = str>>(target: S) -> String {
     String::from(match target.to_lowercase().as_str() {
@@ -12,52 +29,207 @@ fn format_ecma_version_string<S: Deref<Target = str>>(target: S) -> String {
     })
 }
 
-pub fn minify(text: String, options: &Options) -> Option<String> {
-    once_per_thread!(include_script!(TLS_RUNTIME,r"terser.js"));
+fn to_array<'s>(scope: &mut v8::HandleScope<'s>, values: &[String]) -> Option<v8::Local<'s, v8::Value>> {
+    let elements = values.iter().map(|value| v8::String::new(scope, value).map(Into::into)).collect::<Option<Vec<v8::Local<v8::Value>>>>()?;
+    return Some(v8::Array::new_with_elements(scope, &elements).into());
+}
+
+// Returns the minified code, its source map, and any license comments that were extracted (See MinifyOptions::extract_licenses)
+pub fn minify(text: String, options: &Options) -> Result<(String, Option<SourceMap>, Vec<String>), Error> {
+    // Global definitions are substituted by the compressor, so without it they would silently do nothing
+    if !options.minify_options.compress && !options.minify_options.global_defs.is_empty() {
+        return Err(Error::new(Stage::Minify, Location::file(options.filename.clone()), "global definitions require compression to be enabled"));
+    }
+
+    // Terser expects @jridgewell/source-map as the global 'sourceMap'
+    include_script!(TLS_RUNTIME,r"source-map.js")?;
+    include_script!(TLS_RUNTIME,r"terser.js")?;
+    include_script!(TLS_RUNTIME,r"comments.js")?;
+
+    // The lock is held until minification is done so that files minified on other threads see every name this one adds
+    let mut name_cache = if options.minify_options.name_cache {
+        Some(NAME_CACHE.lock().unwrap_or_else(PoisonError::into_inner))
+    } else {
+        None
+    };
 
     return with_v8! {
         use runtime = TLS_RUNTIME;
 
-        let global_this = global_this!();
-        let terser = v8_get!(global_this.Terser)?.to_object(scope!())?;
-        let minify = v8::Local::<v8::Function>::try_from(v8_get!(terser.minify)?.to_object(scope!())?).ok()?;
-    
-        let text = v8_str!(text.as_str());
-
-        // See https://github.com/terser/terser/blob/master/tools/terser.d.ts
-        // https://terser.org/docs/options/
-        let args: v8::Local<v8::Object> = v8_object!({
-            module: v8_bool!(options.module),
-            keep_classnames: v8_bool!(true),
-            compress: v8_object!({
-                ecma: v8_str!(&format_ecma_version_string(options.target.clone()))
-            }),
-            mangle: v8_object!({
-
-            }),
-            format: v8_object!({
-                ecma: v8_str!(&format_ecma_version_string(options.target.clone())),
-                comments: v8_str!("/^!/")
-            })
-        });
-
-        let result = minify.call(scope!(), terser.into(), &[text, args.into()])?;
-
-        if result.is_promise() {
-            let promise = v8::Local::<v8::Promise>::try_from(result).ok()?;
-
-            while promise.state() == v8::PromiseState::Pending {
-                scope!().perform_microtask_checkpoint();
-            }
-            
-            if promise.state() == v8::PromiseState::Rejected {
-                panic!("Promise rejected");
+        let mut name_cache_object: Option<v8::Local<v8::Object>> = None;
+        let mut extracted: Option<v8::Local<v8::Array>> = None;
+
+        let result: Option<v8::Local<v8::Value>> = try {
+            let global_this = global_this!();
+            let terser = v8_get!(global_this.Terser)?.to_object(scope!())?;
+            let minify = v8::Local::<v8::Function>::try_from(v8_get!(terser.minify)?.to_object(scope!())?).ok()?;
+        
+            let text = v8_str!(text.as_str());
+            let minify_options = &options.minify_options;
+
+            // Either option can be false to turn that step off
+            let compress: v8::Local<v8::Value> = if minify_options.compress {
+                let global_defs = v8::Object::new(scope!());
+                for (name, value) in minify_options.global_defs.iter() {
+                    let key: v8::Local<v8::Value> = v8_str!(name.as_str());
+                    let text = v8::String::new(scope!(), value.as_str())?;
+                    let value = match v8::json::parse(scope!(), text) {
+                        Some(value) => value,
+                        None => {
+                            scope!().reset();
+                            text.into()
+                        }
+                    };
+                    global_defs.set(scope!(), key, value)?;
+                }
+
+                let compress: v8::Local<v8::Object> = v8_object!({
+                    global_defs: global_defs.into(),
+                    ecma: v8_str!(&format_ecma_version_string(options.target.clone())),
+                    drop_console: v8_bool!(minify_options.drop_console),
+                    drop_debugger: v8_bool!(minify_options.drop_debugger),
+                    pure_funcs: to_array(scope!(), &minify_options.pure_funcs)?,
+                    passes: v8::Integer::new_from_unsigned(scope!(), minify_options.passes.max(1)).into(),
+                    unsafe_arrows: v8_bool!(minify_options.unsafe_arrows),
+                    unsafe_comps: v8_bool!(minify_options.unsafe_comps),
+                    unsafe_Function: v8_bool!(minify_options.unsafe_function),
+                    unsafe_math: v8_bool!(minify_options.unsafe_math),
+                    unsafe_methods: v8_bool!(minify_options.unsafe_methods),
+                    unsafe_proto: v8_bool!(minify_options.unsafe_proto),
+                    unsafe_regexp: v8_bool!(minify_options.unsafe_regexp),
+                    unsafe_undefined: v8_bool!(minify_options.unsafe_undefined)
+                });
+
+                // 'unsafe' is a keyword, so it cannot be set with v8_object
+                let key: v8::Local<v8::Value> = v8_str!("unsafe");
+                let unsafe_transforms: v8::Local<v8::Value> = v8_bool!(minify_options.unsafe_transforms);
+                compress.set(scope!(), key, unsafe_transforms)?;
+                compress.into()
             } else {
-                let resolved = promise.result(scope!()).to_object(scope!())?;
-                return Some(v8_get!(resolved.code)?.to_string(scope!())?.to_rust_string_lossy(scope!()));
+                v8_bool!(false)
+            };
+
+            let mangle: v8::Local<v8::Value> = if minify_options.mangle {
+                let mangle: v8::Local<v8::Object> = v8_object!({
+                    reserved: to_array(scope!(), &minify_options.reserved)?
+                });
+
+                if minify_options.mangle_properties {
+                    let properties: v8::Local<v8::Object> = v8_object!({
+                        reserved: to_array(scope!(), &minify_options.mangle_properties_reserved)?,
+                        keep_quoted: v8_bool!(minify_options.keep_quoted)
+                    });
+
+                    // Terser accepts the pattern as a string
+                    if let Some(ref regex) = minify_options.mangle_properties_regex {
+                        v8_set!(properties.regex = v8_str!(regex.as_str()));
+                    }
+                    v8_set!(mangle.properties = properties.into());
+                }
+
+                mangle.into()
+            } else {
+                v8_bool!(false)
+            };
+
+            // Kept comments are collected into an array instead when extracting them
+            let comments: v8::Local<v8::Value> = {
+                let global_this = global_this!();
+                let mtsc_comments = v8::Local::<v8::Function>::try_from(v8_get!(global_this.mtscComments)?).ok()?;
+                let condition: v8::Local<v8::Value> = v8_str!(minify_options.comments.as_str());
+                let array: v8::Local<v8::Value> = if minify_options.extract_licenses {
+                    let array = v8::Array::new(scope!(), 0);
+                    extracted = Some(array);
+                    array.into()
+                } else {
+                    v8::undefined(scope!()).into()
+                };
+                mtsc_comments.call(scope!(), global_this.into(), &[condition, array])?
+            };
+
+            // See https://github.com/terser/terser/blob/master/tools/terser.d.ts
+            // https://terser.org/docs/options/
+            let args: v8::Local<v8::Object> = v8_object!({
+                module: v8_bool!(options.module && options.module_kind.is_es_module()),
+                toplevel: v8_bool!(minify_options.toplevel),
+                keep_fnames: v8_bool!(minify_options.keep_fnames),
+                keep_classnames: v8_bool!(minify_options.keep_classnames),
+                compress: compress,
+                mangle: mangle,
+                format: v8_object!({
+                    ecma: v8_str!(&format_ecma_version_string(options.target.clone())),
+                    comments: comments
+                })
+            });
+
+            if options.source_map != SourceMapMode::None {
+                v8_set!(args.sourceMap = v8_object!({}));
+            }
+
+            // Terser reads and updates the cache object in place
+            if let Some(ref name_cache) = name_cache {
+                let object = match name_cache.as_deref() {
+                    Some(json) => {
+                        let json = v8::String::new(scope!(), json)?;
+                        v8::json::parse(scope!(), json)?.to_object(scope!())?
+                    },
+                    None => v8::Object::new(scope!())
+                };
+                v8_set!(args.nameCache = object.into());
+                name_cache_object = Some(object);
             }
+
+            minify.call(scope!(), terser.into(), &[text, args.into()])?
+        };
+
+        let result = result.ok_or_else(|| exception!(Stage::Minify, options.filename.clone()))?;
+
+        let promise = v8::Local::<v8::Promise>::try_from(result).map_err(|_| Error::new(Stage::Minify, Location::file(options.filename.clone()), "value is not a promise"))?;
+
+        while promise.state() == v8::PromiseState::Pending {
+            scope!().perform_microtask_checkpoint();
+        }
+        
+        if promise.state() == v8::PromiseState::Rejected {
+            let reason = promise.result(scope!());
+            Err(super::common::to_error(scope!(), Some(reason), Stage::Minify, options.filename.clone()))
         } else {
-            panic!("Value is not a promise");
+            let code: Option<(String, Option<SourceMap>, Vec<String>)> = try {
+                let resolved = promise.result(scope!()).to_object(scope!())?;
+                let code = v8_get!(resolved.code)?.to_string(scope!())?.to_rust_string_lossy(scope!());
+
+                let source_map = match v8_get!(resolved.map).filter(|value| !value.is_null_or_undefined()) {
+                    Some(map) => Some(to_source_map(scope!(), map)?),
+                    None => None
+                };
+
+                if let (Some(name_cache), Some(object)) = (name_cache.as_mut(), name_cache_object) {
+                    **name_cache = Some(v8::json::stringify(scope!(), object.into())?.to_rust_string_lossy(scope!()));
+                }
+
+                let licenses = match extracted {
+                    Some(array) => super::common::get_strings(scope!(), array.into())?,
+                    None => vec![]
+                };
+
+                (code, source_map, licenses)
+            };
+            code.ok_or_else(|| exception!(Stage::Minify, options.filename.clone()))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn global_defs_without_compression() {
+        let mut options = Options::default();
+        options.minify_options.compress = false;
+        options.minify_options.global_defs.insert(String::from("DEBUG"), String::from("false"));
+
+        let error = minify(String::from("if (DEBUG) console.log(1);"), &options).unwrap_err();
+        assert_eq!(error.stage(), Stage::Minify);
+    }
+}
@@ -1,8 +1,30 @@
 // Shared Code
 use fancy_default::Default;
+#[cfg(any(feature = "minify", feature = "compile"))]
+use std::collections::BTreeMap;
+#[cfg(feature = "serde")]
+use serde::{Serialize,Deserialize};
+
+// Enums are (de)serialized as the same strings the command line takes, through as_str and FromStr
+#[cfg(feature = "serde")]
+macro_rules! serialize_as_str {
+    ($type:ty) => {
+        impl Serialize for $type {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                return serializer.serialize_str(self.as_str());
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $type {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                return String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom);
+            }
+        }
+    };
+}
 
 #[cfg(all(feature = "transpile", feature = "compile"))]
-#[derive(Default,Debug,PartialEq,PartialOrd)]
+#[derive(Clone,Copy,Default,Debug,PartialEq,Eq,PartialOrd,std::hash::Hash)]
 pub enum TSMode {
     #[default]
     Preserve,
@@ -10,14 +32,317 @@ pub enum TSMode {
     Compile,
 }
 
+#[cfg(all(feature = "transpile", feature = "compile"))]
+impl TSMode {
+    // The name used in configs and server requests
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TSMode::Preserve => "preserve",
+            TSMode::Transpile => "transpile",
+            TSMode::Compile => "compile",
+        }
+    }
+}
+
+#[cfg(all(feature = "transpile", feature = "compile"))]
+impl std::str::FromStr for TSMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "preserve" => Ok(TSMode::Preserve),
+            "transpile" => Ok(TSMode::Transpile),
+            "compile" => Ok(TSMode::Compile),
+            _ => Err(format!("unknown ts mode '{}'", s))
+        }
+    }
+}
+
+#[cfg(all(feature = "serde", feature = "transpile", feature = "compile"))]
+serialize_as_str!(TSMode);
+
+#[derive(Clone,Copy,Default,Debug,PartialEq,Eq,std::hash::Hash)]
+pub enum SourceMapMode {
+    #[default]
+    None,
+    Inline,
+    External,
+}
+
+impl SourceMapMode {
+    // The name used by the CLI's --source-map option
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SourceMapMode::None => "none",
+            SourceMapMode::Inline => "inline",
+            SourceMapMode::External => "external",
+        }
+    }
+}
+
+impl std::str::FromStr for SourceMapMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(SourceMapMode::None),
+            "inline" => Ok(SourceMapMode::Inline),
+            "external" => Ok(SourceMapMode::External),
+            _ => Err(format!("unknown source map mode '{}'", s))
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+serialize_as_str!(SourceMapMode);
+
+// The module format of emitted code
+#[cfg(feature = "common")]
+#[derive(Clone,Copy,Default,Debug,PartialEq,Eq,std::hash::Hash)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum ModuleKind {
+    #[default]
+    ESNext,
+    CommonJS,
+    AMD,
+    UMD,
+    System,
+    Node16,
+}
+
+#[cfg(feature = "common")]
+impl ModuleKind {
+    // The name used by TypeScript's module option and the CLI
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModuleKind::ESNext => "esnext",
+            ModuleKind::CommonJS => "commonjs",
+            ModuleKind::AMD => "amd",
+            ModuleKind::UMD => "umd",
+            ModuleKind::System => "system",
+            ModuleKind::Node16 => "node16",
+        }
+    }
+
+    // Whether emitted modules keep their import and export statements
+    pub fn is_es_module(&self) -> bool {
+        matches!(self, ModuleKind::ESNext | ModuleKind::Node16)
+    }
+}
+
+#[cfg(feature = "common")]
+impl std::str::FromStr for ModuleKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "esnext" | "es2015" | "es2020" | "es2022" | "es6" => Ok(ModuleKind::ESNext),
+            "commonjs" | "cjs" => Ok(ModuleKind::CommonJS),
+            "amd" => Ok(ModuleKind::AMD),
+            "umd" => Ok(ModuleKind::UMD),
+            "system" | "systemjs" => Ok(ModuleKind::System),
+            "node16" | "nodenext" => Ok(ModuleKind::Node16),
+            _ => Err(format!("unknown module kind '{}'", s))
+        }
+    }
+}
+
+#[cfg(all(feature = "serde", feature = "common"))]
+serialize_as_str!(ModuleKind);
+
+// How JSX is compiled (See https://www.typescriptlang.org/tsconfig/#jsx)
+#[cfg(any(feature = "transpile", feature = "compile"))]
+#[derive(Clone,Copy,Default,Debug,PartialEq,Eq,std::hash::Hash)]
+pub enum JsxMode {
+    // Keeps JSX as is
+    #[default]
+    Preserve,
+    // Calls Options::jsx_factory and Options::jsx_fragment ('react')
+    Classic,
+    // Imports the runtime from Options::jsx_import_source ('react-jsx')
+    Automatic,
+    // Imports the development runtime from Options::jsx_import_source ('react-jsxdev')
+    AutomaticDev,
+}
+
+#[cfg(any(feature = "transpile", feature = "compile"))]
+impl JsxMode {
+    // The name used by TypeScript's jsx option
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JsxMode::Preserve => "preserve",
+            JsxMode::Classic => "react",
+            JsxMode::Automatic => "react-jsx",
+            JsxMode::AutomaticDev => "react-jsxdev",
+        }
+    }
+}
+
+#[cfg(any(feature = "transpile", feature = "compile"))]
+impl std::str::FromStr for JsxMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "preserve" => Ok(JsxMode::Preserve),
+            "classic" | "react" => Ok(JsxMode::Classic),
+            "automatic" | "react-jsx" => Ok(JsxMode::Automatic),
+            "automatic-dev" | "react-jsxdev" => Ok(JsxMode::AutomaticDev),
+            _ => Err(format!("unknown jsx mode '{}'", s))
+        }
+    }
+}
+
+#[cfg(all(feature = "serde", any(feature = "transpile", feature = "compile")))]
+serialize_as_str!(JsxMode);
+
+// TypeScript compiler options that change emitted code
+// See https://www.typescriptlang.org/tsconfig/#Emit_6246
+#[cfg(any(feature = "transpile", feature = "compile"))]
+#[derive(Clone,Copy,Default,Debug,PartialEq,Eq,std::hash::Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct EmitOptions {
+    pub experimental_decorators: bool,
+    pub emit_decorator_metadata: bool,
+    // When unset, TypeScript enables it for targets of ES2022 and later
+    pub use_define_for_class_fields: Option<bool>,
+    pub import_helpers: bool,
+    pub es_module_interop: bool,
+    pub downlevel_iteration: bool,
+    pub verbatim_module_syntax: bool,
+    pub remove_comments: bool,
+}
+
+// Which comments are kept when minifying
+#[cfg(feature = "minify")]
+#[derive(Clone,Default,Debug,PartialEq,Eq,std::hash::Hash)]
+pub enum CommentMode {
+    None,
+    All,
+    // License comments containing '@license', '@preserve', '@copyright', or '@cc_on' or starting with '!'
+    #[default]
+    Some,
+    // Comments matching a regular expression written as '/pattern/flags' or just a pattern
+    Custom(String),
+}
+
+#[cfg(feature = "minify")]
+impl CommentMode {
+    // The condition passed to mtscComments (See minify/comments.js)
+    pub fn as_str(&self) -> &str {
+        match self {
+            CommentMode::None => "none",
+            CommentMode::All => "all",
+            CommentMode::Some => "some",
+            CommentMode::Custom(regex) => regex,
+        }
+    }
+}
+
+// Anything other than 'none', 'all', or 'some' is a regular expression
+#[cfg(feature = "minify")]
+impl std::str::FromStr for CommentMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" | "false" => Ok(CommentMode::None),
+            "all" | "true" => Ok(CommentMode::All),
+            "some" => Ok(CommentMode::Some),
+            "" => Err(String::from("expected a comment mode or a regular expression")),
+            regex => Ok(CommentMode::Custom(String::from(regex)))
+        }
+    }
+}
+
+#[cfg(all(feature = "serde", feature = "minify"))]
+serialize_as_str!(CommentMode);
+
+// Terser options used when minifying (See https://terser.org/docs/options/)
+#[cfg(feature = "minify")]
+#[derive(Clone,Default,Debug,PartialEq,Eq,std::hash::Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct MinifyOptions {
+    // Compression and mangling can be turned off independently, leaving only whitespace and formatting changes when both are
+    #[default(expr=true)]
+    pub compress: bool,
+    #[default(expr=true)]
+    pub mangle: bool,
+
+    // Compress Options
+    pub drop_console: bool,
+    #[default(expr=true)]
+    pub drop_debugger: bool,
+    // Calls to these functions are removed when their results are unused
+    pub pure_funcs: Vec<String>,
+    #[default(expr=1)]
+    pub passes: u32,
+    // Global constants that are substituted and folded like 'DEBUG' = 'false', with values as JSON text
+    // Values that are not valid JSON are used as strings
+    pub global_defs: BTreeMap<String, String>,
+    // Terser's unsafe transforms, which assume code does not rely on edge cases like overridden built-ins ('unsafe' and 'unsafe_*')
+    pub unsafe_transforms: bool,
+    pub unsafe_arrows: bool,
+    pub unsafe_comps: bool,
+    pub unsafe_function: bool,
+    pub unsafe_math: bool,
+    pub unsafe_methods: bool,
+    pub unsafe_proto: bool,
+    pub unsafe_regexp: bool,
+    pub unsafe_undefined: bool,
+
+    // Mangle Options
+    // Names that are never mangled
+    pub reserved: Vec<String>,
+    // Mangles property names too, which is only safe when every file using them is minified with the same name cache
+    pub mangle_properties: bool,
+    // Only properties matching this pattern are mangled
+    pub mangle_properties_regex: Option<String>,
+    pub mangle_properties_reserved: Vec<String>,
+    // Leaves properties written as quoted strings like obj["name"] alone
+    pub keep_quoted: bool,
+    // Uses the name cache shared by every file minified in this process so that names are mangled the same way in each (See set_name_cache)
+    pub name_cache: bool,
+
+    // Shared Options
+    // Drops and mangles top-level names, which is only safe when nothing outside the file uses them
+    pub toplevel: bool,
+    pub keep_fnames: bool,
+    #[default(expr=true)]
+    pub keep_classnames: bool,
+
+    // Format Options
+    pub comments: CommentMode,
+    // Moves kept comments into Output::licenses instead of the output, to be written to a '.LICENSE.txt' file
+    pub extract_licenses: bool,
+}
+
+// With the serde feature, options are (de)serialized with their field names as keys and enum values as strings that
+// the command line also takes (e.g. {"source_map": "external", "module_kind": "commonjs", "jsx_mode": "react-jsxdev", "comments": "/^!/"})
+// Missing fields take their default values, and diagnostic_sink is never serialized
+// Fields only exist when their feature is enabled, so configs written for a build with more features may contain unknown fields,
+// which update_options_from_config reports as warnings
 #[derive(Clone,Default,Debug,std::hash::Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Options {
+    // Used by the preprocessor and for error locations
+    pub filename: Option<String>,
+    // Receives warnings and errors; when unset, nothing is printed and only a diagnostic that fails compilation is returned as an Error
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub diagnostic_sink: Option<crate::diagnostic::DiagnosticSink>,
+    pub source_map: SourceMapMode,
+
     #[cfg(feature = "common")]
     #[default(expr=String::from("es2022"))]
     pub target: String,
     #[cfg(feature = "common")]
     pub module: bool,
+    // Only affects files that TypeScript processes; other module kinds wrap ES modules so that minification treats them as scripts
+    #[cfg(feature = "common")]
+    pub module_kind: ModuleKind,
 
     // Compile and Transpile Features
     #[cfg(all(feature = "transpile", feature = "compile"))]
@@ -29,16 +354,38 @@ pub struct Options {
     #[default(expr=true)]
     pub transpile: bool,
 
+    // Fail on error diagnostics from TypeScript instead of emitting best-effort code
+    #[cfg(any(feature = "transpile", feature = "compile"))]
+    pub fail_on_error: bool,
+
+    // Emit a declaration file alongside the code (Per file and isolatedDeclarations-style unless type checking)
+    #[cfg(any(feature = "transpile", feature = "compile"))]
+    pub declaration: bool,
+
+    // Parse JSX; how it is compiled depends on jsx_mode (See Options::resolved_jsx_mode when it is unset)
     #[cfg(any(feature = "transpile", feature = "compile"))]
     pub use_jsx: bool,
     #[cfg(any(feature = "transpile", feature = "compile"))]
+    pub jsx_mode: Option<JsxMode>,
+    #[cfg(any(feature = "transpile", feature = "compile"))]
     pub jsx_factory: Option<String>,
     #[cfg(any(feature = "transpile", feature = "compile"))]
     pub jsx_fragment: Option<String>,
+    #[cfg(any(feature = "transpile", feature = "compile"))]
+    pub jsx_import_source: Option<String>,
+
+    #[cfg(any(feature = "transpile", feature = "compile"))]
+    pub emit: EmitOptions,
+
+    // TypeScript options that only affect type checking like strict or noImplicitAny, passed to TypeScript as is (e.g. from tsconfig.json)
+    #[cfg(feature = "compile")]
+    pub type_check_options: BTreeMap<String, bool>,
 
     // Minify Feature
     #[cfg(feature = "minify")]
     pub minify: bool,
+    #[cfg(feature = "minify")]
+    pub minify_options: MinifyOptions,
 
     // Preprocess Feature
     #[cfg(feature = "preprocess")]
@@ -46,15 +393,42 @@ pub struct Options {
     #[cfg(feature = "preprocess")]
     pub macros: Vec<String>,
     #[cfg(feature = "preprocess")]
-    pub filename: Option<String>,
-    #[cfg(feature = "preprocess")]
     pub include_paths: Vec<String>,
 
     // HTML Feature
     #[cfg(feature = "html")]
     pub html: bool,
+
+    // Text added to the start and end of compiled code after minification, or '@' and a file to read it from (See banner.rs for placeholders)
+    // In HTML files, these are added to each script instead
+    pub banner: Option<String>,
+    pub footer: Option<String>,
 }
 
+#[cfg(any(feature = "transpile", feature = "compile"))]
+impl Options {
+    // How JSX is compiled; without a jsx_mode, a jsx_factory means 'react' and otherwise JSX is kept as is
+    pub fn resolved_jsx_mode(&self) -> JsxMode {
+        return self.jsx_mode.unwrap_or(if self.jsx_factory.is_some() {JsxMode::Classic} else {JsxMode::Preserve});
+    }
+}
+
+// The result of the compile or transpile feature
+#[cfg(any(feature = "transpile", feature = "compile"))]
+pub struct TSOutput {
+    pub code: String,
+    pub source_map: Option<crate::sourcemap::SourceMap>,
+    pub declaration: Option<String>,
+    pub diagnostics: Vec<crate::Diagnostic>,
+    // Type checking fails on any error regardless of Options::fail_on_error
+    pub fail_on_error: bool,
+    // Files read while type checking, such as imported modules
+    pub dependencies: Vec<String>,
+}
+
+#[cfg(any(feature = "transpile", feature = "compile"))]
+pub type TSStage = fn(String, &Options) -> Result<TSOutput, crate::Error>;
+
 // Shared V8 Code
 #[cfg(feature = "common")]
 mod common;
@@ -80,7 +454,7 @@ pub use transpile::transpile;
 #[cfg(feature = "minify")]
 mod minify;
 #[cfg(feature = "minify")]
-pub use minify::minify;
+pub use minify::{minify,name_cache,set_name_cache};
 
 
 // Preprocessor Feature 
@@ -96,4 +470,111 @@ compile_error!("feature \"html\" requires feature \"compile\" and/or feature \"t
 #[cfg(feature = "html")]
 mod html;
 #[cfg(feature = "html")]
-pub use html::compile_html;
\ No newline at end of file
+pub use html::compile_html;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Options made without OptionsBuilder::jsx, like struct literals and deserialized configs, still use their factory
+    #[cfg(any(feature = "transpile", feature = "compile"))]
+    #[test]
+    fn jsx_factory_without_mode() {
+        let options = Options { use_jsx: true, jsx_factory: Some(String::from("h")), ..Default::default() };
+        assert_eq!(options.resolved_jsx_mode(), JsxMode::Classic);
+
+        let options = Options { use_jsx: true, ..Default::default() };
+        assert_eq!(options.resolved_jsx_mode(), JsxMode::Preserve);
+
+        let options = Options { use_jsx: true, jsx_mode: Some(JsxMode::Preserve), jsx_factory: Some(String::from("h")), ..Default::default() };
+        assert_eq!(options.resolved_jsx_mode(), JsxMode::Preserve);
+    }
+
+    #[cfg(feature = "transpile")]
+    #[test]
+    fn jsx_factory_without_mode_is_compiled() {
+        #[allow(unused_mut)]
+        let mut options = Options { use_jsx: true, jsx_factory: Some(String::from("h")), filename: Some(String::from("input.tsx")), ..Default::default() };
+        #[cfg(feature = "compile")]
+        {
+            options.ts = TSMode::Transpile;
+        }
+        let code = crate::compile("const element = <div/>;", &options).unwrap();
+        assert!(code.contains("h(\"div\""), "{}", code);
+    }
+
+    #[cfg(all(feature = "config", any(feature = "transpile", feature = "compile")))]
+    #[test]
+    fn jsx_factory_without_mode_from_toml() {
+        let options: Options = toml::from_str("use_jsx = true\njsx_factory = \"h\"").unwrap();
+        assert_eq!(options.jsx_mode, None);
+        assert_eq!(options.resolved_jsx_mode(), JsxMode::Classic);
+    }
+
+    #[cfg(feature = "config")]
+    fn round_trip<T: Serialize + for<'de> Deserialize<'de> + PartialEq + std::fmt::Debug>(value: T, expected: &str) {
+        let serialized = toml::Value::try_from(&value).unwrap();
+        assert_eq!(serialized, toml::Value::String(String::from(expected)));
+        assert_eq!(serialized.try_into::<T>().unwrap(), value);
+    }
+
+    #[cfg(all(feature = "config", feature = "transpile", feature = "compile"))]
+    #[test]
+    fn ts_mode_round_trip() {
+        for mode in [TSMode::Preserve, TSMode::Transpile, TSMode::Compile].iter() {
+            round_trip(*mode, mode.as_str());
+        }
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn source_map_mode_round_trip() {
+        for mode in [SourceMapMode::None, SourceMapMode::Inline, SourceMapMode::External].iter() {
+            assert_eq!(mode.as_str().parse::<SourceMapMode>(), Ok(*mode));
+            round_trip(*mode, mode.as_str());
+        }
+    }
+
+    #[cfg(all(feature = "config", feature = "common"))]
+    #[test]
+    fn module_kind_round_trip() {
+        for kind in [ModuleKind::ESNext, ModuleKind::CommonJS, ModuleKind::AMD, ModuleKind::UMD, ModuleKind::System, ModuleKind::Node16].iter() {
+            assert_eq!(kind.as_str().parse::<ModuleKind>(), Ok(*kind));
+            round_trip(*kind, kind.as_str());
+        }
+        assert_eq!(toml::Value::from("cjs").try_into::<ModuleKind>().unwrap(), ModuleKind::CommonJS);
+    }
+
+    #[cfg(all(feature = "config", any(feature = "transpile", feature = "compile")))]
+    #[test]
+    fn jsx_mode_round_trip() {
+        for mode in [JsxMode::Preserve, JsxMode::Classic, JsxMode::Automatic, JsxMode::AutomaticDev].iter() {
+            assert_eq!(mode.as_str().parse::<JsxMode>(), Ok(*mode));
+            round_trip(*mode, mode.as_str());
+        }
+        assert_eq!(toml::Value::from("automatic-dev").try_into::<JsxMode>().unwrap(), JsxMode::AutomaticDev);
+    }
+
+    #[cfg(all(feature = "config", feature = "minify"))]
+    #[test]
+    fn comment_mode_round_trip() {
+        for mode in [CommentMode::None, CommentMode::All, CommentMode::Some, CommentMode::Custom(String::from("/^!/i"))].iter() {
+            assert_eq!(mode.as_str().parse::<CommentMode>().as_ref(), Ok(mode));
+            round_trip(mode.clone(), mode.as_str());
+        }
+        assert_eq!(toml::Value::from("false").try_into::<CommentMode>().unwrap(), CommentMode::None);
+        assert!(toml::Value::from("").try_into::<CommentMode>().is_err());
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn option_source_round_trip() {
+        for source in [crate::util::OptionSource::Mime(String::from("text/html")), crate::util::OptionSource::Path(std::path::PathBuf::from("index.html"))].iter() {
+            let serialized = toml::Value::try_from(source).unwrap();
+            assert_eq!(serialized.try_into::<crate::util::OptionSource>().unwrap(), *source);
+        }
+        let serialized = toml::Value::try_from(crate::util::OptionSource::None).unwrap();
+        assert_eq!(serialized, toml::Value::String(String::from("none")));
+        assert_eq!(serialized.try_into::<crate::util::OptionSource>().unwrap(), crate::util::OptionSource::None);
+    }
+}
@@ -1,7 +1,86 @@
 use crate::Options;
+use crate::error::Error;
+use crate::sourcemap::SourceMap;
 
 mod wave;
 
-pub fn preprocess(text: String, options: &Options) -> Option<String> {
-    return wave::preprocess_text(text, options.filename.clone(), options.macros.clone(), options.include_paths.clone());
-}
\ No newline at end of file
+// Returns the preprocessed text and a line map back to the original files
+// The files that were included or embedded are added to dependencies even if preprocessing fails, such as on an #error in a header
+pub fn preprocess(text: String, options: &Options, dependencies: &mut Vec<String>) -> Result<(String, SourceMap), Error> {
+    let (text, line_map, included, diagnostics) = wave::preprocess_text(text, options.filename.clone(), options.macros.clone(), options.include_paths.clone());
+    dependencies.extend(included);
+    crate::diagnostic::report(diagnostics, options.diagnostic_sink.as_ref(), true)?;
+    return Ok((text, line_map));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Writes a header that is removed when dropped, so that tests running in parallel do not share one
+    struct Header(std::path::PathBuf);
+
+    impl Header {
+        fn new(name: &str, text: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("mtsc-test-{}-{}.h", name, std::process::id()));
+            std::fs::write(&path, text).unwrap();
+            return Header(path);
+        }
+
+        fn path(&self) -> String {
+            return self.0.to_string_lossy().into_owned();
+        }
+
+        fn name(&self) -> String {
+            return self.0.file_name().unwrap().to_string_lossy().into_owned();
+        }
+    }
+
+    impl Drop for Header {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn included_lines_map_to_their_file() {
+        let header = Header::new("line-map", "const first = 1;\nconst second = 2;\n");
+        let options = Options { filename: Some(String::from("input.ts")), ..Default::default() };
+        let (text, line_map) = preprocess(format!("const a = 0;\n#include \"{}\"\nconst b = 3;\n", header.path()), &options, &mut vec![]).unwrap();
+
+        let position = |needle: &str| {
+            let line = text.lines().position(|line| line.contains(needle)).unwrap();
+            let original = line_map.lookup(line as u32, 0).unwrap();
+            return (line_map.sources[original.source as usize].clone(), original.line);
+        };
+
+        let (source, line) = position("second");
+        assert!(source.ends_with(&header.name()), "{}", source);
+        assert_eq!(line, 1);
+
+        let (source, line) = position("const b");
+        assert!(source.ends_with("input.ts"), "{}", source);
+        assert_eq!(line, 2);
+    }
+
+    // Errors found after preprocessing point at the included file rather than the preprocessed text
+    #[cfg(feature = "transpile")]
+    #[test]
+    fn errors_in_included_files() {
+        let header = Header::new("error", "const first = 1;\nconst broken = ;\n");
+        #[allow(unused_mut)]
+        let mut options = Options { filename: Some(String::from("input.ts")), preprocess: true, fail_on_error: true, ..Default::default() };
+        #[cfg(feature = "compile")]
+        {
+            options.ts = crate::TSMode::Transpile;
+        }
+        #[cfg(not(feature = "compile"))]
+        {
+            options.transpile = true;
+        }
+
+        let error = crate::compile(format!("const a = 0;\n#include \"{}\"\nconst b = 3;\n", header.path()), &options).unwrap_err();
+        assert!(error.filename().map_or(false, |filename| filename.ends_with(&header.name())), "{:?}", error);
+        assert_eq!(error.line(), Some(2));
+    }
+}
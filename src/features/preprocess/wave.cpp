@@ -7,6 +7,7 @@
 #include <stack>
 #include <filesystem>
 #include <algorithm>
+#include <stdexcept>
 
 // Static wave configuration
 #define BOOST_WAVE_SUPPORT_CPP1Z 1
@@ -100,13 +101,14 @@ class wave_hooks : public boost::wave::context_policies::eat_whitespace<TokenT>
         message_callback on_message;
         position_type& current_position;
         iterator_type*& iter;                 // reference to a pointer to an iterator
+        std::vector<std::string>& dependencies; // files reached through #include and #embed
         
         struct {
             bool flag;
             std::string text;
         } eval_state = {false, ""};
     public:
-        wave_hooks(const bool PRESERVE_WHITESPACE, const bool PRESERVE_BOL_WHITESPACE, message_callback on_message, position_type& current_position, iterator_type*& iter) : PRESERVE_WHITESPACE(PRESERVE_WHITESPACE), PRESERVE_BOL_WHITESPACE(PRESERVE_BOL_WHITESPACE), on_message(on_message), current_position(current_position), iter(iter) {}
+        wave_hooks(const bool PRESERVE_WHITESPACE, const bool PRESERVE_BOL_WHITESPACE, message_callback on_message, position_type& current_position, iterator_type*& iter, std::vector<std::string>& dependencies) : PRESERVE_WHITESPACE(PRESERVE_WHITESPACE), PRESERVE_BOL_WHITESPACE(PRESERVE_BOL_WHITESPACE), on_message(on_message), current_position(current_position), iter(iter), dependencies(dependencies) {}
 
         template<typename ContextT>
         bool may_skip_whitespace(ContextT const &ctx, TokenT &token, bool &skipped_newline) {
@@ -126,6 +128,11 @@ class wave_hooks : public boost::wave::context_policies::eat_whitespace<TokenT>
             return base_type::locate_include_file(ctx,file_path,is_system,current_name,dir_path,native_name);
         }
 
+        template<typename ContextT>
+        void opened_include_file(ContextT const& ctx, std::string const& relname, std::string const& absname, bool is_system_include) {
+            dependencies.push_back(absname);
+        }
+
         template<typename ContextT, typename ContainerT>
         bool interpret_pragma(ContextT& ctx, ContainerT &pending, TokenT const& option, ContainerT const& values, TokenT const& act_token) {
             if(option.get_value() == "eval") {
@@ -232,6 +239,7 @@ class wave_hooks : public boost::wave::context_policies::eat_whitespace<TokenT>
                     if(!this->locate_include_file(ctx,value,false,NULL,dir,path)) {
                         return false;
                     }
+                    dependencies.push_back(std::filesystem::absolute(std::filesystem::path(path)).string());
 
                     ContainerT data;
 
@@ -292,14 +300,69 @@ std::string& apply_input_adjustment(std::string &text, const bool DISCARD_HASHBA
     return text;
 }
 
-std::string& apply_output_adjustment(std::string &text) {
-    auto replace = [&](const boost::regex& regex, const std::string& replacement) {
-        return text = boost::regex_replace(text,regex,replacement);
-    };
+struct line_origin {
+    std::string file;
+    i32 line;
+};
+
+// Whether the next non-blank line starting at start is a directive
+// The whole-text lookahead '\s*?#' this replaced could match across blank lines, so they are skipped here too
+bool directive_follows(const std::vector<std::string>& lines, size_t start) {
+    for(size_t i = start; i < lines.size(); i++) {
+        std::string::size_type first = lines[i].find_first_not_of(" \t\r\n\f\v");
+        if(first != std::string::npos) {
+            return lines[i][first] == '#';
+        }
+    }
+    return false;
+}
+
+// Applied line by line rather than with regexes over the whole text so that the origin of each line is kept
+std::string& apply_output_adjustment(std::string &text, std::vector<line_origin>& origins) {
+    const std::string SPLIT_MARKER = UFFFF "91;";
+    const boost::regex BLOCK_MARKER(UFFFF "17;");
+
+    std::vector<std::string> lines;
+    std::string::size_type start = 0, end;
+    while((end = text.find('\n', start)) != std::string::npos) {
+        lines.push_back(boost::regex_replace(text.substr(start, end - start), BLOCK_MARKER, ""));   // Unblock normal #...
+        start = end + 1;
+    }
+    lines.push_back(boost::regex_replace(text.substr(start), BLOCK_MARKER, ""));
+
+    // Every line gets an origin while preprocessing, so a mismatch means the line map would be wrong from here on
+    if(origins.size() != lines.size()) {
+        throw std::logic_error("preprocessed " + std::to_string(lines.size()) + " lines but recorded the origins of " + std::to_string(origins.size()));
+    }
+
+    std::string result;
+    std::vector<line_origin> result_origins;
+
+    for(size_t i = 0; i < lines.size(); i++) {
+        std::string line = lines[i];
+        line_origin origin = origins[i];
+
+        std::string::size_type marker;
+        while(i + 1 < lines.size() && (marker = line.rfind(SPLIT_MARKER)) != std::string::npos
+            && (line.length() == marker + SPLIT_MARKER.length() || line.substr(marker + SPLIT_MARKER.length()) == "\r")) {
+            line.erase(marker);
 
-    replace(boost::regex(UFFFF "17;"), "");                                     // Unblock normal #...
-    replace(boost::regex(UFFFF "91;\\r?\\n(?=\\s*?#)"), "///");                 // Unsplit unused ///#
-    replace(boost::regex(UFFFF "91;\\r?\\n"), "");                              // Discard used ///# splits
+            if(directive_follows(lines, i + 1)) {
+                line += "///";                                                  // Unsplit unused ///#
+            }
+            line += lines[++i];                                                 // Discard used ///# splits
+            origin = origins[i];
+        }
+
+        if(!result_origins.empty()) {
+            result += "\n";
+        }
+        result += line;
+        result_origins.push_back(origin);
+    }
+
+    text = result;
+    origins = result_origins;
 
     return text;
 }
@@ -353,7 +416,7 @@ const char* get_current_exception_name() {
     return abi::__cxa_demangle(abi::__cxa_current_exception_type()->name(), 0, 0, &status);
 }
 
-std::string _preprocess_text(std::string text, const char* p_filename, const std::vector<std::string> MACROS, const std::vector<std::string> INCLUDE_PATHS, message_callback on_message) {
+std::string _preprocess_text(std::string text, const char* p_filename, const std::vector<std::string> MACROS, const std::vector<std::string> INCLUDE_PATHS, message_callback on_message, std::vector<line_origin>& origins, std::vector<std::string>& dependencies) {
     boost::wave::util::file_position_type current_position;
 
     try {
@@ -366,7 +429,7 @@ std::string _preprocess_text(std::string text, const char* p_filename, const std
         apply_input_adjustment(text);
 
         iterator_type* iter;
-        context_type ctx(text.begin(), text.end(), p_filename, wave_hooks<token_type>(true, true, on_message, current_position, iter));
+        context_type ctx(text.begin(), text.end(), p_filename, wave_hooks<token_type>(true, true, on_message, current_position, iter, dependencies));
 
         // Configure features
         #define ENABLE(f) ctx.set_language(boost::wave::enable_##f(ctx.get_language()))
@@ -423,6 +486,28 @@ std::string _preprocess_text(std::string text, const char* p_filename, const std
         iterator_type first = ctx.begin(), last = ctx.end();
         std::stringstream out_stream;
 
+        // Records where the first token of each output line came from (including included files)
+        bool at_line_start = true;
+        auto record_origins = [&](const std::string& value) {
+            i32 line = current_position.get_line();
+            std::string file = current_position.get_file().c_str();
+
+            if(at_line_start) {
+                origins.push_back({file, line});
+                at_line_start = false;
+            }
+
+            for(size_t i = 0; i < value.length(); i++) {
+                if(value[i] == '\n') {
+                    if(i + 1 < value.length()) {
+                        origins.push_back({file, ++line});
+                    } else {
+                        at_line_start = true;
+                    }
+                }
+            }
+        };
+
         bool need_to_advance = false, finished = false;
         do {
             try {
@@ -433,7 +518,9 @@ std::string _preprocess_text(std::string text, const char* p_filename, const std
                 while(first != last) {
                     iter=&first;
                     current_position = (*first).get_position();
-                    out_stream << (*first).get_value();
+                    std::string value = (*first).get_value().c_str();
+                    record_origins(value);
+                    out_stream << value;
                     ++first;
                 }
                 finished = true;
@@ -456,9 +543,14 @@ std::string _preprocess_text(std::string text, const char* p_filename, const std
                 }
             }
         } while(!finished);
+
+        // The last line has no token to record it when the output is empty or ends with a newline
+        if(at_line_start) {
+            origins.push_back({current_position.get_file().c_str(), (i32)current_position.get_line()});
+        }
         
         std::string result = out_stream.str();
-        apply_output_adjustment(result);
+        apply_output_adjustment(result, origins);
 
         return hashbang + result;
     }
@@ -478,9 +570,9 @@ std::string _preprocess_text(std::string text, const char* p_filename, const std
 }
 
 namespace wave {
-    rust::String preprocess_text(rust::String text, rust::String filename, const rust::Vec<rust::String> MACROS, const rust::Vec<rust::String> INCLUDE_PATHS) {
-        message_callback on_message = [](const MessageType TYPE, const std::string FILENAME, const i32 LINE, const std::string MESSAGE) {
-           callback((i32)TYPE,FILENAME,LINE,MESSAGE);
+    PreprocessResult preprocess_text(rust::String text, rust::String filename, const rust::Vec<rust::String> MACROS, const rust::Vec<rust::String> INCLUDE_PATHS, Diagnostics& diagnostics) {
+        message_callback on_message = [&diagnostics](const MessageType TYPE, const std::string FILENAME, const i32 LINE, const std::string MESSAGE) {
+           diagnostics.push((i32)TYPE,FILENAME,LINE,MESSAGE);
         };
         
         std::vector<std::string> macros;
@@ -492,6 +584,22 @@ namespace wave {
         std::transform(INCLUDE_PATHS.begin(), INCLUDE_PATHS.end(), std::back_inserter(paths), [](const rust::String& str) { return std::string(str); });
 
 
-        return _preprocess_text(std::string(text), filename.c_str(), macros, paths, on_message);
+        std::vector<line_origin> origins;
+        std::vector<std::string> dependencies;
+        std::string result = _preprocess_text(std::string(text), filename.c_str(), macros, paths, on_message, origins, dependencies);
+
+        rust::Vec<LineOrigin> lines;
+        lines.reserve(origins.size());
+        for(const line_origin& origin : origins) {
+            lines.push_back(LineOrigin{rust::String(origin.file), origin.line});
+        }
+
+        rust::Vec<rust::String> dependency_paths;
+        dependency_paths.reserve(dependencies.size());
+        for(const std::string& dependency : dependencies) {
+            dependency_paths.push_back(rust::String(dependency));
+        }
+
+        return PreprocessResult{rust::String(result), std::move(lines), std::move(dependency_paths)};
     }
 }
\ No newline at end of file
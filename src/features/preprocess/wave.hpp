@@ -6,6 +6,12 @@
 typedef int32_t i32;
 
 #include "rust/cxx.h"
+
+namespace wave {
+    struct Diagnostics;
+    struct PreprocessResult;
+}
+
 #include "mtsc/src/features/preprocess/wave.rs.h"
 
 namespace wave {
@@ -16,5 +22,5 @@ namespace wave {
         EXCEPTION = 3
     };
 
-    rust::String preprocess_text(rust::String text, rust::String filename, const rust::Vec<rust::String> MACROS, const rust::Vec<rust::String> INCLUDE_PATHS);
+    PreprocessResult preprocess_text(rust::String text, rust::String filename, const rust::Vec<rust::String> MACROS, const rust::Vec<rust::String> INCLUDE_PATHS, Diagnostics& diagnostics);
 }
\ No newline at end of file
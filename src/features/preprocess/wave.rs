@@ -1,5 +1,8 @@
 use std::vec::Vec;
-use std::process::exit;
+
+use crate::diagnostic::{Diagnostic,Severity};
+use crate::error::{Stage,Location};
+use crate::sourcemap::{SourceMap,Segment,OriginalPosition};
 
 #[allow(unused)]
 pub enum MessageType {
@@ -11,30 +14,76 @@ pub enum MessageType {
 #[cxx::bridge(namespace = "wave")]
 mod ffi {
     // Common types
+    struct LineOrigin {
+        filename: String,
+        line: i32,
+    }
+
+    struct PreprocessResult {
+        text: String,
+        lines: Vec<LineOrigin>,
+        dependencies: Vec<String>,
+    }
 
     // Rust types exposed to C++
     extern "Rust" {
-        fn callback(message_type: i32, filename: String, line: i32, message: String);
+        type Diagnostics;
+        fn push(self: &mut Diagnostics, message_type: i32, filename: String, line: i32, message: String);
     }
 
     // C++ types exposed to Rust
     unsafe extern "C++" {
         include!("mtsc/src/features/preprocess/wave.hpp");
-        fn preprocess_text(text: String, filename: String, macros: Vec<String>, include_paths: Vec<String>) -> String;
+        fn preprocess_text(text: String, filename: String, macros: Vec<String>, include_paths: Vec<String>, diagnostics: &mut Diagnostics) -> PreprocessResult;
     }
 }
 
-fn callback(message_type: i32, filename: String, line: i32, message: String) {
-    match message_type {
-        i if i == MessageType::ERROR as i32 => {
-            eprintln!("\x1b[91mpreprocessor error\x1b[0m: {} ({}:{})", message.trim_start_matches("error: ").trim().split(' ').filter(|s| !s.is_empty()).collect::<Vec<_>>().join(" "), filename, line);
-            exit(3);
-        },
-        i if i == MessageType::WARNING as i32 => eprintln!("\x1b[93mpreprocessor warning\x1b[0m: {} ({}:{})", message.trim_start_matches("warning: ").trim().split(' ').filter(|s| !s.is_empty()).collect::<Vec<_>>().join(" "), filename, line),
-        _ => panic!("{} at {filename}:{line}", message.trim_start_matches("error: "))
-    };
+#[derive(Default)]
+pub struct Diagnostics(Vec<Diagnostic>);
+
+impl Diagnostics {
+    fn push(&mut self, message_type: i32, filename: String, line: i32, message: String) {
+        let severity = match message_type {
+            i if i == MessageType::WARNING as i32 => Severity::Warning,
+            _ => Severity::Error
+        };
+
+        let message = message.trim_start_matches("error: ").trim_start_matches("warning: ").trim().split(' ').filter(|s| !s.is_empty()).collect::<Vec<_>>().join(" ");
+        let filename = Some(filename).filter(|filename| filename != "<stdin>");
+        let line = Some(line).filter(|line| *line > 0).map(|line| line as usize);
+
+        self.0.push(Diagnostic::new(Stage::Preprocess, severity, Location::new(filename, line, None), message));
+    }
 }
 
-pub fn preprocess_text(text: String, filename: Option<String>, macros: Vec<String>, include_paths: Vec<String>) -> Option<String> {
-    return Some(ffi::preprocess_text(text, filename.unwrap_or(String::from("<stdin>")), macros, include_paths));
-}
\ No newline at end of file
+// Maps each line of the output to the file and line it came from
+fn line_map(lines: Vec<ffi::LineOrigin>) -> SourceMap {
+    let mut sources: Vec<String> = vec![];
+    let mappings = lines.into_iter().map(|origin| {
+        let source = match sources.iter().position(|source| *source == origin.filename) {
+            Some(i) => i,
+            None => {
+                sources.push(origin.filename);
+                sources.len() - 1
+            }
+        };
+        vec![Segment { column: 0, original: Some(OriginalPosition { source: source as u32, line: (origin.line.max(1) - 1) as u32, column: 0, name: None }) }]
+    }).collect();
+
+    return SourceMap { sources, mappings, ..Default::default() };
+}
+
+// Returns the preprocessed text, where each line came from, every file reached through #include or #embed, and any diagnostics
+pub fn preprocess_text(text: String, filename: Option<String>, macros: Vec<String>, include_paths: Vec<String>) -> (String, SourceMap, Vec<String>, Vec<Diagnostic>) {
+    let mut diagnostics = Diagnostics::default();
+    let result = ffi::preprocess_text(text, filename.unwrap_or(String::from("<stdin>")), macros, include_paths, &mut diagnostics);
+
+    let mut dependencies: Vec<String> = vec![];
+    for dependency in result.dependencies {
+        if dependency != "<stdin>" && !dependencies.contains(&dependency) {
+            dependencies.push(dependency);
+        }
+    }
+
+    return (result.text, line_map(result.lines), dependencies, diagnostics.0);
+}
@@ -1,35 +1,72 @@
 // Transpile Feature
-use super::common::{with_v8,include_script,once_per_thread,TLS_RUNTIME};
+use super::common::{with_v8,include_script,compiler_options,to_source_map,to_diagnostics,strip_source_mapping_url,TLS_RUNTIME};
+use super::TSOutput;
 use crate::Options;
+use crate::error::{Error,Stage};
 
 use std::convert::TryFrom;
 
-pub fn transpile(text: String, options: &Options) -> Option<String> {
-    once_per_thread!(include_script!(TLS_RUNTIME, r"typescript.js"));
+pub fn transpile(text: String, options: &Options) -> Result<TSOutput, Error> {
+    include_script!(TLS_RUNTIME, r"typescript.js")?;
+    include_script!(TLS_RUNTIME, r"../ts-diagnostics.js")?;
 
     return with_v8! {
         use runtime = TLS_RUNTIME;
-        
-        let global_this = global_this!();
-        let ts = v8_get!(global_this.ts)?.to_object(scope!())?;
-        let transpile = v8::Local::<v8::Function>::try_from(v8_get!(ts.transpile)?.to_object(scope!())?).ok()?;
-    
-        let text = v8_str!(text.as_str());
-    
-        let args: v8::Local<v8::Object> = v8_object!({
-            target: v8_str!(options.target.as_str()),
-            module: v8_str!("esnext")
-        });
-    
-        if options.use_jsx {
-            v8_set!(args.jsx = v8_str!(if options.jsx_factory.is_some() {"react"} else {"preserve"}));
-            
-            if options.jsx_factory.is_some() {
-                v8_set!(args.jsxFactory = v8_str!(options.jsx_factory.clone().unwrap().as_str()));
-                v8_set!(args.jsxFragmentFactory = v8_str!(options.jsx_fragment.clone().unwrap().as_str()));
+
+        let result: Option<TSOutput> = try {
+            let global_this = global_this!();
+            let ts = v8_get!(global_this.ts)?.to_object(scope!())?;
+            let transpile_module = v8::Local::<v8::Function>::try_from(v8_get!(ts.transpileModule)?.to_object(scope!())?).ok()?;
+
+            let text = v8_str!(text.as_str());
+
+            let compiler_options = compiler_options(scope!(), options)?;
+
+            let args: v8::Local<v8::Object> = v8_object!({
+                compilerOptions: compiler_options.into(),
+                reportDiagnostics: v8_bool!(true)
+            });
+
+            if let Some(ref filename) = options.filename {
+                v8_set!(args.fileName = v8_str!(filename.as_str()));
             }
-        }
-    
-        return Some(transpile.call(scope!(), ts.into(), &[text, args.into()])?.to_rust_string_lossy(scope!()))
+
+            let output = transpile_module.call(scope!(), ts.into(), &[text, args.into()])?.to_object(scope!())?;
+            let output_text = v8_get!(output.outputText)?.to_rust_string_lossy(scope!());
+
+            let source_map = match v8_get!(output.sourceMapText).filter(|value| !value.is_null_or_undefined()) {
+                Some(source_map_text) => Some(to_source_map(scope!(), source_map_text)?),
+                None => None
+            };
+
+            let to_diagnostics_js = v8::Local::<v8::Function>::try_from(v8_get!(global_this.mtscToDiagnostics)?).ok()?;
+            let diagnostics = v8_get!(output.diagnostics).unwrap_or_else(|| v8::Array::new(scope!(), 0).into());
+            let diagnostics = to_diagnostics_js.call(scope!(), global_this.into(), &[diagnostics])?;
+            let mut diagnostics = to_diagnostics(scope!(), diagnostics, Stage::Transpile)?;
+
+            // Declarations are emitted from the same file without type information, so exports need explicit types
+            let declaration = if options.declaration {
+                let transpile_declaration = v8::Local::<v8::Function>::try_from(v8_get!(ts.transpileDeclaration)?).ok()?;
+                let output = transpile_declaration.call(scope!(), ts.into(), &[text, args.into()])?.to_object(scope!())?;
+                let declaration_diagnostics = v8_get!(output.diagnostics).unwrap_or_else(|| v8::Array::new(scope!(), 0).into());
+                let declaration_diagnostics = to_diagnostics_js.call(scope!(), global_this.into(), &[declaration_diagnostics])?;
+                diagnostics.extend(to_diagnostics(scope!(), declaration_diagnostics, Stage::Transpile)?);
+
+                Some(strip_source_mapping_url(v8_get!(output.outputText)?.to_rust_string_lossy(scope!())))
+            } else {
+                None
+            };
+
+            TSOutput {
+                code: strip_source_mapping_url(output_text),
+                source_map,
+                declaration,
+                diagnostics,
+                fail_on_error: false,
+                dependencies: vec![],
+            }
+        };
+
+        result.ok_or_else(|| exception!(Stage::Transpile, options.filename.clone()))
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,22 @@
+// Shared by the compile and transpile features
+// Converts a ts.Diagnostic into a plain object that is read back into a crate::diagnostic::Diagnostic
+globalThis.mtscToDiagnostic = function(diagnostic) {
+    const result = {
+        category: ts.DiagnosticCategory[diagnostic.category],
+        code: diagnostic.code,
+        message: ts.flattenDiagnosticMessageText(diagnostic.messageText, '\n'),
+    };
+
+    if(diagnostic.file && diagnostic.start !== undefined) {
+        const {line, character} = diagnostic.file.getLineAndCharacterOfPosition(diagnostic.start);
+        result.file = diagnostic.file.fileName;
+        result.line = line + 1;
+        result.column = character + 1;
+    }
+
+    return result;
+};
+
+globalThis.mtscToDiagnostics = function(diagnostics) {
+    return diagnostics.map(mtscToDiagnostic);
+};
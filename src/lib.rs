@@ -1,59 +1,283 @@
 #![feature(macro_metavar_expr)]
+#![feature(try_blocks)]
 mod features;
-pub use features::Options;
+pub use features::{Options,SourceMapMode};
+
+pub mod error;
+pub use error::Error;
+
+pub mod diagnostic;
+pub use diagnostic::Diagnostic;
+
 #[cfg(all(feature = "compile", feature = "transpile"))]
 pub use features::TSMode;
 
+#[cfg(any(feature = "compile", feature = "transpile"))]
+pub use features::{EmitOptions,JsxMode};
+
 #[cfg(feature = "common")]
-pub use features::init_v8;
+pub use features::{init_v8,ModuleKind};
+
+#[cfg(feature = "minify")]
+pub use features::{MinifyOptions,CommentMode,name_cache,set_name_cache};
 
 pub mod util;
 
+pub mod cache;
+pub use cache::Cache;
+
+mod builder;
+pub use builder::OptionsBuilder;
+
+mod banner;
+
+mod pool;
+
+mod sourcemap;
+use sourcemap::SourceMap;
+
 use cfg_if::cfg_if;
 
-pub fn compile_script<T: AsRef<str>>(text: T, options: &Options) -> Option<String> {
-    let mut text = String::from(text.as_ref());
+// The result of compiling a file; source_map is only set when Options::source_map is not SourceMapMode::None
+// With SourceMapMode::External, no sourceMappingURL comment is added since the map's final location is up to the caller
+// declaration is only set when Options::declaration is enabled (Declaration file inputs are passed through as is and produce no code)
+// dependencies lists every file read through the preprocessor's #include and #embed directives, along with any banner or footer file
+// licenses holds the comments removed by MinifyOptions::extract_licenses, ready to be written to a LICENSE.txt file
+#[derive(Clone,Default,Debug,PartialEq,Eq)]
+pub struct Output {
+    pub code: String,
+    pub source_map: Option<String>,
+    pub declaration: Option<String>,
+    pub dependencies: Vec<String>,
+    pub licenses: Option<String>,
+}
 
-    #[cfg(feature = "preprocess")]
-    if options.preprocess {
-        text = features::preprocess(text,&options)?;
+// Joins extracted license comments into the text of a LICENSE.txt file, or None if there are none
+#[allow(unused)]
+fn licenses_text(licenses: Vec<String>) -> Option<String> {
+    if licenses.is_empty() {
+        return None;
     }
+    return Some(format!("{}\n", licenses.join("\n\n")));
+}
+
+// The result of compiling a script before minification
+struct ScriptOutput {
+    code: String,
+    source_map: Option<SourceMap>,
+    declaration: Option<String>,
+}
 
+// Picks the TypeScript stage to run, if any
+#[cfg(any(feature = "compile", feature = "transpile"))]
+fn typescript_stage(options: &Options) -> Option<features::TSStage> {
     cfg_if! {
         if #[cfg(all(feature = "compile", feature = "transpile"))] {
-            text = match options.ts {
-                TSMode::Compile => features::compile(text,&options)?,
-                TSMode::Transpile => features::transpile(text,&options)?,
-                _ => text
-            }
+            return match options.ts {
+                TSMode::Compile => Some(features::compile as features::TSStage),
+                TSMode::Transpile => Some(features::transpile as features::TSStage),
+                TSMode::Preserve => None
+            };
         } else if #[cfg(feature = "compile")] {
-            if options.compile {
-                text = features::compile(text,&options)?;
-            }
-        } else if #[cfg(feature = "transpile")] {
-            if options.transpile {
-                text = features::transpile(text,&options)?;
-            }
+            return Some(features::compile as features::TSStage).filter(|_| options.compile);
+        } else {
+            return Some(features::transpile as features::TSStage).filter(|_| options.transpile);
         }
     }
+}
+
+// Files that are read are added to dependencies as they are read so that they are known even if compiling fails
+#[allow(unused_variables)]
+fn compile_script_internal<T: AsRef<str>>(text: T, options: &Options, dependencies: &mut Vec<String>) -> Result<ScriptOutput, Error> {
+    let mut text = String::from(text.as_ref());
+    #[allow(unused_mut)]
+    let mut source_map: Option<SourceMap> = None;
+    #[allow(unused_mut)]
+    let mut line_map: Option<SourceMap> = None;
+    #[allow(unused_mut)]
+    let mut declaration: Option<String> = None;
+
+    #[cfg(feature = "preprocess")]
+    if options.preprocess {
+        let (preprocessed_text, preprocessed_line_map) = features::preprocess(text,&options,dependencies)?;
+        text = preprocessed_text;
+        line_map = Some(preprocessed_line_map);
+    }
+
+    // Errors in later stages should point at the file that was included rather than the preprocessed output
+    #[allow(unused)]
+    let remap_location = |location: &mut error::Location| {
+        if let Some(ref line_map) = line_map {
+            line_map.remap_location(location);
+        }
+    };
 
-    return Some(text);
+    // Declaration files have no code to emit
+    #[cfg(any(feature = "compile", feature = "transpile"))]
+    if options.filename.as_ref().is_some_and(|filename| util::is_declaration_path(std::path::Path::new(filename))) {
+        return Ok(ScriptOutput { code: String::new(), source_map: None, declaration: Some(text).filter(|_| options.declaration) });
+    }
+
+    #[cfg(any(feature = "compile", feature = "transpile"))]
+    if let Some(stage) = typescript_stage(options) {
+        let mut output = stage(text,&options).map_err(|mut error| {
+            remap_location(error.location_mut());
+            error
+        })?;
+
+        // Recorded before reporting so that fixing an imported module after a type error triggers a rebuild
+        dependencies.extend(output.dependencies.drain(..));
+        output.diagnostics.iter_mut().for_each(|diagnostic| remap_location(&mut diagnostic.location));
+        diagnostic::report(output.diagnostics, options.diagnostic_sink.as_ref(), options.fail_on_error || output.fail_on_error)?;
+
+        text = output.code;
+        source_map = output.source_map;
+        declaration = output.declaration;
+    }
+
+    source_map = match (source_map, line_map) {
+        (Some(outer), Some(inner)) => Some(outer.compose(&inner)),
+        (None, Some(line_map)) if options.source_map != SourceMapMode::None => Some(line_map),
+        (source_map, _) => source_map
+    };
+
+    return Ok(ScriptOutput { code: text, source_map, declaration });
 }
 
-pub fn compile<T: AsRef<str>>(text: T, options: &Options) -> Option<String> {
+pub fn compile_script<T: AsRef<str>>(text: T, options: &Options) -> Result<String, Error> {
+    return compile_script_internal(text, options, &mut vec![]).map(|output| output.code);
+}
+
+pub fn compile_output<T: AsRef<str>>(text: T, options: &Options) -> Result<Output, Error> {
+    return compile_output_internal(text, options, &mut vec![]);
+}
+
+// Like compile_output, but also returns the files that were read when compiling fails (e.g. a header with an #error in it)
+// On success, these are the same as Output::dependencies
+pub fn compile_output_with_dependencies<T: AsRef<str>>(text: T, options: &Options) -> (Result<Output, Error>, Vec<String>) {
+    let mut dependencies = vec![];
+    let result = compile_output_internal(text, options, &mut dependencies);
+    return (result, dependencies);
+}
+
+fn compile_output_internal<T: AsRef<str>>(text: T, options: &Options, dependencies: &mut Vec<String>) -> Result<Output, Error> {
+    // Banner and footer files are read last, but an unreadable one should still be watched
+    dependencies.extend(banner::dependencies(options));
+
     #[cfg(feature = "html")]
     if options.html {
-        return features::compile_html(String::from(text.as_ref()), options);
+        let (code, licenses) = features::compile_html(String::from(text.as_ref()), options, dependencies)?;
+        return Ok(Output {
+            code,
+            dependencies: dependencies.clone(),
+            licenses: licenses_text(licenses),
+            ..Default::default()
+        });
     }
     
-    let text = compile_script(text,options)?;
+    #[allow(unused_mut)]
+    let ScriptOutput { mut code, mut source_map, declaration } = compile_script_internal(text.as_ref(),options,dependencies)?;
+    #[allow(unused_mut)]
+    let mut licenses: Option<String> = None;
 
     #[cfg(feature = "minify")]
     if options.minify {
-        return features::minify(text,&options);
+        let (minified_code, minified_source_map, extracted_licenses) = features::minify(code,&options)?;
+        code = minified_code;
+        licenses = licenses_text(extracted_licenses);
+        source_map = match (minified_source_map, source_map) {
+            (Some(outer), Some(inner)) => Some(outer.compose(&inner)),
+            (outer, _) => outer
+        };
+    }
+
+    // Nothing is added to empty outputs like those of declaration file inputs
+    if !code.is_empty() {
+        code = banner::apply(code, source_map.as_mut(), options)?;
+    }
+
+    let dependencies = dependencies.clone();
+    if options.source_map == SourceMapMode::None {
+        return Ok(Output { code, source_map: None, declaration, dependencies, licenses });
+    }
+
+    let source_map = source_map.map(|mut source_map| {
+        if source_map.sources.len() == 1 && source_map.sources_content.is_empty() {
+            source_map.sources_content = vec![Some(String::from(text.as_ref()))];
+        }
+        source_map
+    });
+
+    if let (SourceMapMode::Inline, Some(ref source_map)) = (options.source_map, &source_map) {
+        if !code.ends_with('\n') {
+            code.push('\n');
+        }
+        code.push_str(&format!("//# sourceMappingURL={}\n", source_map.to_data_url()));
+    }
+
+    return Ok(Output { code, source_map: source_map.map(|source_map| source_map.to_json()), declaration, dependencies, licenses });
+}
+
+pub fn compile<T: AsRef<str>>(text: T, options: &Options) -> Result<String, Error> {
+    return compile_output(text, options).map(|output| output.code);
+}
+
+// A panic while compiling one input becomes an error for that input so that the others still finish
+fn compile_isolated(text: &str, options: &Options) -> (Result<Output, Error>, Vec<String>) {
+    return std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| compile_output_with_dependencies(text, options)))
+        .unwrap_or_else(|_| (Err(worker_panicked(options)), vec![]));
+}
+
+fn worker_panicked(options: &Options) -> Error {
+    return Error::new(error::Stage::V8Init, error::Location::file(options.filename.clone()), "worker panicked while compiling");
+}
+
+// Compiles many files across a pool of worker threads and returns their results in input order (See compile_output_with_dependencies)
+// Workers are kept between calls, so each loads TypeScript and Terser into its own runtime once per process (See pool.rs)
+// An error in one file does not stop the others, and jobs is the number of workers, where 0 uses one worker per CPU
+// With a single job, files are compiled on the calling thread instead
+pub fn compile_many<T: AsRef<str> + Sync>(inputs: &[(T, Options)], jobs: usize) -> Vec<(Result<Output, Error>, Vec<String>)> {
+    use std::sync::{Arc,mpsc};
+    use std::sync::atomic::{AtomicUsize,Ordering};
+
+    let jobs = match jobs {
+        0 => std::thread::available_parallelism().map_or(1, |jobs| jobs.get()),
+        jobs => jobs
+    }.min(inputs.len()).max(1);
+
+    if jobs == 1 {
+        return inputs.iter().map(|(text, options)| compile_isolated(text.as_ref(), options)).collect();
+    }
+
+    // Workers outlive this call, so they get their own copy of the inputs
+    let owned: Arc<Vec<(String, Options)>> = Arc::new(inputs.iter().map(|(text, options)| (String::from(text.as_ref()), options.clone())).collect());
+
+    // Each job takes the next input until there are none left, so slow files do not hold up a whole share of the inputs
+    let next = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = mpsc::channel();
+
+    pool::run(jobs, (0..jobs).map(|_| {
+        let (owned, next, sender) = (owned.clone(), next.clone(), sender.clone());
+        Box::new(move || loop {
+            let i = next.fetch_add(1, Ordering::Relaxed);
+            let Some((text, options)) = owned.get(i) else {
+                break;
+            };
+
+            let _ = sender.send((i, compile_isolated(text, options)));
+        }) as Box<dyn FnOnce() + Send>
+    }).collect());
+    drop(sender);
+
+    let mut results: Vec<Option<(Result<Output, Error>, Vec<String>)>> = inputs.iter().map(|_| None).collect();
+    for (i, result) in receiver {
+        results[i] = Some(result);
     }
 
-    return Some(text);
+    // A slot is only empty if its worker died outside of compile_isolated
+    return results.into_iter().zip(inputs.iter())
+        .map(|(result, (_, options))| result.unwrap_or_else(|| (Err(worker_panicked(options)), vec![])))
+        .collect();
 }
 
 pub fn version() -> &'static str {
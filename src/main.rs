@@ -1,17 +1,257 @@
 use std::io::{self, Read as _};
-use std::path::PathBuf;
+use std::path::{Path,PathBuf};
 use std::process::exit;
 use std::panic;
 use std::fs;
+use std::sync::Mutex;
+use std::collections::HashMap;
+use std::thread;
+use std::time::{Duration,Instant,SystemTime};
 
 use clap::{Arg, App};
 use backtrace::Backtrace;
 use same_file::is_same_file;
-use or_panic::OrPanic as _;
 
-use mtsc::{compile,Options};
+use mtsc::{Output,Options,SourceMapMode,EmitOptions,JsxMode,MinifyOptions,CommentMode,Error,Diagnostic};
+#[cfg(all(feature = "transpile", feature = "compile"))]
+use mtsc::TSMode;
+use mtsc::error::{Stage,Location};
+use mtsc::diagnostic::{Action,DiagnosticSink,Severity};
+
+// Errors that have already been printed as diagnostics so that fail does not print them twice
+static REPORTED_ERRORS: Mutex<Vec<Error>> = Mutex::new(Vec::new());
+
+// Prints diagnostics in a compiler-style 'file:line:col: error TS1005: message' format
+fn print_diagnostic(diagnostic: &Diagnostic) -> Action {
+    let severity = match diagnostic.severity {
+        Severity::Error => "\x1b[91;1merror\x1b[0m",
+        Severity::Warning => "\x1b[93;1mwarning\x1b[0m",
+        Severity::Message => "\x1b[96;1mmessage\x1b[0m"
+    };
+
+    match diagnostic.code {
+        Some(code) => eprintln!("{}: {} TS{}: {}", diagnostic.location, severity, code, diagnostic.message),
+        None => eprintln!("{}: {}: {}", diagnostic.location, severity, diagnostic.message)
+    }
+
+    if diagnostic.is_error() {
+        if let Ok(mut reported_errors) = REPORTED_ERRORS.lock() {
+            reported_errors.push(diagnostic.clone().into_error());
+        }
+    }
+
+    return Action::Continue;
+}
+
+fn report_error(error: &Error) {
+    if !REPORTED_ERRORS.lock().map(|reported_errors| reported_errors.contains(error)).unwrap_or_default() {
+        eprintln!("\x1b[91;1merror\x1b[0m: {}", error);
+    }
+}
+
+// Checks flags that take a count like --jobs so that a typo is not silently treated as the default
+fn validate_count(value: String, minimum: u32) -> Result<(), String> {
+    return match value.parse::<u32>() {
+        Ok(count) if count >= minimum => Ok(()),
+        _ => Err(format!("expected a whole number of at least {} but found '{}'", minimum, value))
+    };
+}
+
+fn exit_code(error: &Error) -> i32 {
+    return match error.stage() {
+        Stage::Preprocess => 3,
+        _ => 1
+    };
+}
+
+fn fail(error: Error) -> ! {
+    report_error(&error);
+    exit(exit_code(&error));
+}
+
+// A file to compile (or stdin when path is None); root is the directory that is mirrored in the output directory
+struct Input {
+    path: Option<PathBuf>,
+    root: PathBuf,
+}
+
+// An input that is ready to compile (unless skip is set) along with where its output goes
+struct Job {
+    options: Options,
+    maybe_filename: Option<String>,
+    output_path: Option<PathBuf>,
+    is_declaration_input: bool,
+    skip: bool,
+    dependencies: Vec<PathBuf>,
+}
+
+// Expands directories (recursively) and glob patterns into the files to compile
+fn collect_inputs(values: Vec<&str>) -> Result<Vec<Input>, Error> {
+    let mut inputs = Vec::new();
+
+    for value in values {
+        let path = PathBuf::from(value);
+
+        if value == "-" {
+            if inputs.iter().any(|input: &Input| input.path.is_none()) {
+                return Err(Error::new(Stage::Io, Location::default(), "stdin can only be used as an input once"));
+            }
+            inputs.push(Input { path: None, root: PathBuf::from(".") });
+        } else if path.is_dir() {
+            let files = mtsc::util::glob::walk(&path).map_err(|e| Error::io(Some(value), e))?;
+            inputs.extend(files.into_iter().filter(|file| mtsc::util::is_source_path(file)).map(|file| Input { path: Some(file), root: path.clone() }));
+        } else if !path.exists() && mtsc::util::glob::is_glob(value) {
+            let root = mtsc::util::glob::Glob::new(value).base();
+            let files = mtsc::util::glob::glob(value).map_err(|e| Error::io(Some(value), e))?;
+
+            if files.is_empty() {
+                return Err(Error::new(Stage::Io, Location::default(), format!("no files match '{}'", value)));
+            }
+            inputs.extend(files.into_iter().map(|file| Input { path: Some(file), root: root.clone() }));
+        } else {
+            let root = path.parent().map(PathBuf::from).unwrap_or_default();
+            inputs.push(Input { path: Some(path), root });
+        }
+    }
+
+    return Ok(inputs);
+}
+
+// A request read by the server; only text is required
+#[cfg(feature = "server")]
+#[derive(serde::Deserialize)]
+struct Request {
+    // Echoed back so that responses can be matched up with requests
+    #[serde(default)]
+    id: serde_json::Value,
+    text: String,
+    filename: Option<String>,
+    #[serde(default)]
+    options: Options,
+    // Enables features based on the file name like the command line does
+    #[serde(default = "default_infer")]
+    infer: bool,
+}
+
+#[cfg(feature = "server")]
+fn default_infer() -> bool {
+    true
+}
+
+#[cfg(feature = "server")]
+fn location_json(location: &Location) -> serde_json::Value {
+    return serde_json::json!({
+        "file": location.filename,
+        "line": location.line,
+        "column": location.column,
+    });
+}
+
+#[cfg(feature = "server")]
+fn diagnostic_json(diagnostic: &Diagnostic) -> serde_json::Value {
+    return serde_json::json!({
+        "stage": diagnostic.stage.to_string(),
+        "severity": diagnostic.severity.to_string(),
+        "code": diagnostic.code,
+        "location": location_json(&diagnostic.location),
+        "message": diagnostic.message,
+    });
+}
+
+#[cfg(feature = "server")]
+fn error_json(error: &Error) -> serde_json::Value {
+    return serde_json::json!({
+        "stage": error.stage().to_string(),
+        "location": location_json(error.location()),
+        "message": error.message(),
+    });
+}
+
+// Compiles a single request, collecting its diagnostics instead of printing them
+#[cfg(feature = "server")]
+fn handle_request(line: &str) -> serde_json::Value {
+    let start = Instant::now();
+
+    let request: Request = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => return serde_json::json!({
+            "id": null,
+            "ok": false,
+            "error": error_json(&Error::new(Stage::Config, Location::default(), format!("invalid request: {}", e))),
+            "diagnostics": [],
+        })
+    };
+
+    let diagnostics: std::sync::Arc<Mutex<Vec<Diagnostic>>> = Default::default();
+    let mut options = request.options;
+    options.filename = request.filename.or(options.filename);
+    options.diagnostic_sink = Some(DiagnosticSink::new({
+        let diagnostics = diagnostics.clone();
+        move |diagnostic| {
+            diagnostics.lock().unwrap().push(diagnostic.clone());
+            Action::Continue
+        }
+    }));
+
+    if let (true, Some(filename)) = (request.infer, options.filename.clone()) {
+        mtsc::util::update_options(mtsc::util::OptionSource::Path(PathBuf::from(filename)), &mut options, &mtsc::util::all_options());
+    }
+
+    let (result, dependencies) = mtsc::compile_output_with_dependencies(&request.text, &options);
+    let diagnostics: Vec<serde_json::Value> = diagnostics.lock().unwrap().iter().map(diagnostic_json).collect();
+    let time = start.elapsed().as_secs_f64() * 1000.0;
+
+    return match result {
+        Ok(output) => serde_json::json!({
+            "id": request.id,
+            "ok": true,
+            "code": output.code,
+            "source_map": output.source_map,
+            "declaration": output.declaration,
+            "dependencies": output.dependencies,
+            "licenses": output.licenses,
+            "diagnostics": diagnostics,
+            "time_ms": time,
+        }),
+        Err(error) => serde_json::json!({
+            "id": request.id,
+            "ok": false,
+            "error": error_json(&error),
+            "dependencies": dependencies,
+            "diagnostics": diagnostics,
+            "time_ms": time,
+        })
+    };
+}
+
+// Reads newline-delimited JSON requests from stdin and writes a JSON response line to stdout for each of them
+// Requests are compiled one at a time on this thread so that TypeScript and Terser stay loaded between them
+#[cfg(feature = "server")]
+fn run_server() {
+    use std::io::{BufRead as _, Write as _};
+
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+
+    for line in stdin.lock().lines() {
+        let line = line.unwrap_or_else(|e| fail(Error::io(None::<String>, e)));
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = handle_request(&line);
+        let mut stdout = stdout.lock();
+        writeln!(stdout, "{}", response).and_then(|_| stdout.flush()).unwrap_or_else(|e| fail(Error::io(None::<String>, e)));
+    }
+}
 
 fn main() {
+    if panic::catch_unwind(run).is_err() {
+        exit(1);
+    }
+}
+
+fn run() {
     // CLI options
     let matches = App::new("MTSC")
         .version(clap::crate_version!())
@@ -34,11 +274,20 @@ fn main() {
             .help("Treat the input as a modern ES module (Enabled by default for '.mts' files and HTML script tags with type 'tsmodule')")
         )
 
+        .arg(Arg::with_name("module-kind")
+            .long("module-kind")
+            .value_name("KIND")
+            .help("Sets the module format of compiled code (Only affects code compiled from TypeScript; modules are only minified as ES modules when the format is 'esnext' or 'node16')")
+            .possible_values(&["esnext", "commonjs", "amd", "umd", "system", "node16"])
+            .default_value("esnext")
+            .takes_value(true)
+        )
+
         .arg(Arg::with_name("jsx")
             .short("x")
             .long("jsx")
             .value_name("JSX-FACTORY")
-            .help("Sets the JSX factory for compiled code (When this option is set but blank or when the file extension is '.tsx', JSX is preserved as is; otherwise, it is interpreted as standard code)")
+            .help("Sets the JSX factory for compiled code (When this option is set but blank or when the file extension is '.tsx', JSX is preserved as is; otherwise, it is interpreted as standard code; see the jsx-mode option for the automatic runtime)")
             .default_value("")
             .hide_default_value(true)
             .takes_value(true)
@@ -47,8 +296,22 @@ fn main() {
         .arg(Arg::with_name("jsx-fragment")
             .long("jsx-fragment")
             .value_name("JSX-FRAGMENT")
-            .help("Sets the JSX fragment factory for compiled code (Requires the jsx option to be set as well)")
-            .default_value("null")
+            .help("Sets the JSX fragment factory for compiled code (Only used when the JSX mode is 'classic')")
+            .takes_value(true)
+        )
+
+        .arg(Arg::with_name("jsx-mode")
+            .long("jsx-mode")
+            .value_name("MODE")
+            .help("Sets how JSX is compiled: 'preserve' keeps it as is, 'classic' calls the JSX factory, and 'automatic' or 'automatic-dev' import React 17's runtime from the JSX import source (Defaults to 'classic' when a JSX factory is given and 'preserve' otherwise; also accepts TypeScript's names 'react', 'react-jsx', and 'react-jsxdev')")
+            .possible_values(&["preserve", "classic", "automatic", "automatic-dev", "react", "react-jsx", "react-jsxdev"])
+            .takes_value(true)
+        )
+
+        .arg(Arg::with_name("jsx-import-source")
+            .long("jsx-import-source")
+            .value_name("MODULE")
+            .help("Sets the module the automatic JSX runtime is imported from, such as 'preact' (Defaults to 'react')")
             .takes_value(true)
         )
 
@@ -95,18 +358,291 @@ fn main() {
             .takes_value(true)
         )
 
+        .arg(Arg::with_name("outdir")
+            .long("outdir")
+            .value_name("DIRECTORY")
+            .help("Sets the directory to write output files to, mirroring the layout of input directories and glob patterns (Extensions are updated as with the out option)")
+            .takes_value(true)
+            .conflicts_with("output")
+        )
+
         .arg(Arg::with_name("minify")
             .short("M")
             .long("minify")
             .help("Enables minification using Terser (both compression and mangling) of output code (Except for HTML files, '.min' is prepend to the output file extension)")
         )
 
+        .arg(Arg::with_name("no-compress")
+            .long("no-compress")
+            .help("Turns off compression when minifying")
+        )
+
+        .arg(Arg::with_name("no-mangle")
+            .long("no-mangle")
+            .help("Turns off name mangling when minifying")
+        )
+
+        .arg(Arg::with_name("drop-console")
+            .long("drop-console")
+            .help("Removes calls to console functions when minifying")
+        )
+
+        .arg(Arg::with_name("keep-debugger")
+            .long("keep-debugger")
+            .help("Keeps debugger statements when minifying")
+        )
+
+        .arg(Arg::with_name("pure-funcs")
+            .long("pure-funcs")
+            .value_name("NAME")
+            .help("Marks a function as free of side effects so that calls to it can be removed when their results are unused (e.g. 'Math.floor' or 'console.info')")
+            .takes_value(true)
+            .number_of_values(1)
+            .multiple(true)
+        )
+
+        .arg(Arg::with_name("passes")
+            .long("passes")
+            .value_name("N")
+            .help("Sets the number of times code is compressed when minifying (More passes may produce smaller output)")
+            .validator(|value| validate_count(value, 1))
+            .takes_value(true)
+        )
+
+        .arg(Arg::with_name("define-global")
+            .long("define-global")
+            .value_name("NAME=VALUE")
+            .help("Defines a global constant that is substituted and folded when minifying so that code like 'if (DEBUG)' can be removed (Values are JSON such as 'false', '2', or '\"text\"' and are used as strings if they are not valid JSON; leaving out the value defines it as 'true'; requires compression; unrelated to the preprocessor's macros)")
+            .conflicts_with("no-compress")
+            .takes_value(true)
+            .number_of_values(1)
+            .multiple(true)
+        )
+
+        .arg(Arg::with_name("toplevel")
+            .long("toplevel")
+            .help("Drops and mangles top-level names when minifying (Only safe when nothing outside the file uses them)")
+        )
+
+        .arg(Arg::with_name("keep-fnames")
+            .long("keep-fnames")
+            .help("Keeps function names when minifying")
+        )
+
+        .arg(Arg::with_name("unsafe")
+            .long("unsafe")
+            .value_name("TRANSFORM")
+            .help("Enables one of Terser's unsafe transforms when minifying, which assume code does not rely on edge cases like overridden built-ins ('unsafe' enables Terser's 'unsafe' option and 'all' enables every transform)")
+            .possible_values(&["all", "unsafe", "arrows", "comps", "function", "math", "methods", "proto", "regexp", "undefined"])
+            .takes_value(true)
+            .number_of_values(1)
+            .multiple(true)
+        )
+
+        .arg(Arg::with_name("reserved")
+            .long("reserved")
+            .value_name("NAME")
+            .help("Keeps a name from being mangled when minifying")
+            .takes_value(true)
+            .number_of_values(1)
+            .multiple(true)
+        )
+
+        .arg(Arg::with_name("mangle-props")
+            .long("mangle-props")
+            .help("Mangles property names when minifying (Only safe when every file using them is minified together with a name cache)")
+        )
+
+        .arg(Arg::with_name("mangle-props-regex")
+            .long("mangle-props-regex")
+            .value_name("REGEX")
+            .help("Only mangles properties matching a pattern (e.g. '^_' for properties starting with an underscore)")
+            .takes_value(true)
+        )
+
+        .arg(Arg::with_name("mangle-props-reserved")
+            .long("mangle-props-reserved")
+            .value_name("NAME")
+            .help("Keeps a property from being mangled")
+            .takes_value(true)
+            .number_of_values(1)
+            .multiple(true)
+        )
+
+        .arg(Arg::with_name("keep-quoted")
+            .long("keep-quoted")
+            .help("Keeps properties written as quoted strings like obj[\"name\"] from being mangled")
+        )
+
+        .arg(Arg::with_name("name-cache")
+            .long("name-cache")
+            .value_name("FILE")
+            .help("Mangles names the same way in every file by reading Terser's name cache from a file before compiling and writing it back after (The file is created if it does not exist)")
+            .takes_value(true)
+        )
+
+        .arg(Arg::with_name("comments")
+            .long("comments")
+            .value_name("MODE")
+            .help("Sets which comments are kept when minifying: 'none', 'all', 'some' for license comments like /*! ... */ or those with @license or @preserve, or a regex like '/@license/i' (Defaults to 'some')")
+            .takes_value(true)
+        )
+
+        .arg(Arg::with_name("extract-licenses")
+            .long("extract-licenses")
+            .help("Moves the comments kept when minifying into a separate file next to the output (e.g. 'app.min.js.LICENSE.txt')")
+        )
+
         .arg(Arg::with_name("html")
             .long("html")
             .short("H")
             .help("Treat the input as an HTML file and transpile any script tags with the type attribute set to 'text/typescript' or 'tsmodule' (Enabled by default for '.html' files)")
         )
 
+        .arg(Arg::with_name("banner")
+            .long("banner")
+            .value_name("TEXT")
+            .help("Adds text like a copyright comment to the start of the output after minification, or to each script in HTML files (Use '@FILE' to read it from a file; '[file]', '[path]', '[version]', and '[date]' are replaced with the input's file name, its path, MTSC's version, and the date)")
+            .takes_value(true)
+        )
+
+        .arg(Arg::with_name("footer")
+            .long("footer")
+            .value_name("TEXT")
+            .help("Adds text to the end of the output after minification, like --banner does to the start")
+            .takes_value(true)
+        )
+
+        .arg(Arg::with_name("type-check")
+            .short("c")
+            .long("type-check")
+            .help("Type checks the input before emitting code and fails on any type errors (Requires MTSC to be built with the 'compile' feature)")
+        )
+
+        .arg(Arg::with_name("fail-on-error")
+            .long("fail-on-error")
+            .help("Fails instead of emitting best-effort code when TypeScript reports any errors (Errors are always fatal when type checking)")
+        )
+
+        .arg(Arg::with_name("declaration")
+            .short("d")
+            .long("declaration")
+            .help("Emits a declaration file next to the output file with the extension updated to '.d.ts' (Unless type checking, exported declarations need explicit type annotations as with TypeScript's isolatedDeclarations; declaration file inputs are copied to the output path or skipped)")
+        )
+
+        .arg(Arg::with_name("experimental-decorators")
+            .long("experimental-decorators")
+            .help("Enables TypeScript's legacy experimental decorators instead of standard decorators")
+        )
+
+        .arg(Arg::with_name("emit-decorator-metadata")
+            .long("emit-decorator-metadata")
+            .help("Emits design-type metadata for decorated declarations (Requires the experimental-decorators option and a reflect-metadata polyfill at runtime)")
+        )
+
+        .arg(Arg::with_name("use-define-for-class-fields")
+            .long("use-define-for-class-fields")
+            .value_name("BOOL")
+            .help("Sets whether class fields use standard define semantics instead of assignments in the constructor (Defaults to true for targets of ES2022 and later)")
+            .possible_values(&["true", "false"])
+            .takes_value(true)
+        )
+
+        .arg(Arg::with_name("import-helpers")
+            .long("import-helpers")
+            .help("Imports helper functions from 'tslib' instead of inlining them in each file")
+        )
+
+        .arg(Arg::with_name("es-module-interop")
+            .long("es-module-interop")
+            .help("Emits helpers for importing CommonJS modules as if they were ES modules")
+        )
+
+        .arg(Arg::with_name("downlevel-iteration")
+            .long("downlevel-iteration")
+            .help("Uses the full iteration protocol for spreads and for-of loops when targeting ES5 and earlier")
+        )
+
+        .arg(Arg::with_name("verbatim-module-syntax")
+            .long("verbatim-module-syntax")
+            .help("Only removes imports and exports marked with the 'type' modifier instead of eliding unused ones")
+        )
+
+        .arg(Arg::with_name("remove-comments")
+            .long("remove-comments")
+            .help("Removes comments from compiled code")
+        )
+
+        .arg(Arg::with_name("source-map")
+            .long("source-map")
+            .value_name("MODE")
+            .help("Generates a source map pointing back to the original input (When set to 'external', the map is written next to the output file with '.map' appended to its name and linked with a sourceMappingURL comment; when set to 'inline', it is embedded in the output as a data URL; ignored for HTML files)")
+            .possible_values(&["none", "inline", "external"])
+            .default_value("none")
+            .takes_value(true)
+        )
+
+        .arg(Arg::with_name("tsconfig")
+            .long("tsconfig")
+            .value_name("PATH")
+            .help("Sets the tsconfig.json to read compiler options from instead of searching the input file's directory and its parents (Options set on the command line take precedence)")
+            .takes_value(true)
+        )
+
+        .arg(Arg::with_name("no-tsconfig")
+            .long("no-tsconfig")
+            .help("Disables reading compiler options from tsconfig.json")
+            .conflicts_with("tsconfig")
+        )
+
+        .arg(Arg::with_name("config")
+            .long("config")
+            .value_name("PATH")
+            .help("Reads options from an mtsc.toml file (Defaults to the nearest mtsc.toml to each input; options from the command line take precedence)")
+            .takes_value(true)
+        )
+
+        .arg(Arg::with_name("no-config")
+            .long("no-config")
+            .help("Ignores any mtsc.toml files")
+            .conflicts_with("config")
+        )
+
+        .arg(Arg::with_name("print-config")
+            .long("print-config")
+            .value_name("FILE")
+            .help("Prints the options a file would be compiled with as TOML instead of compiling anything")
+            .takes_value(true)
+        )
+
+        .arg(Arg::with_name("jobs")
+            .short("j")
+            .long("jobs")
+            .value_name("N")
+            .help("Sets the number of files compiled in parallel, each on a thread with its own copy of TypeScript and Terser loaded that is kept between rebuilds in watch mode (Set to 0 to use one thread per CPU)")
+            .default_value("1")
+            .validator(|value| validate_count(value, 0))
+            .takes_value(true)
+        )
+
+        .arg(Arg::with_name("cache-dir")
+            .long("cache-dir")
+            .value_name("DIRECTORY")
+            .help("Reuses outputs stored in a directory when the input, options and included files are unchanged")
+            .takes_value(true)
+        )
+
+        .arg(Arg::with_name("watch")
+            .short("w")
+            .long("watch")
+            .help("Keeps running and recompiles inputs when they or any files they include change (New files in input directories and matching glob patterns are compiled as they appear)")
+        )
+
+        .arg(Arg::with_name("server")
+            .long("server")
+            .help("Compiles newline-delimited JSON requests from stdin and writes a JSON response for each to stdout instead of compiling inputs (Requests look like {\"id\": 1, \"text\": \"...\", \"filename\": \"a.ts\", \"options\": {...}})")
+        )
+
         .arg(Arg::with_name("verbose")
             .short("V")
             .long("verbose")
@@ -114,8 +650,9 @@ fn main() {
         )
 
         .arg(Arg::with_name("INPUT")
-            .help("Sets the input file to compile (Leave blank or set to '-' to read from stdin)")
+            .help("Sets the input files to compile (Leave blank or set to '-' to read from stdin; directories are searched recursively for TypeScript and preprocessor files, and glob patterns such as 'src/**/*.ts' are expanded)")
             .index(1)
+            .multiple(true)
         )
         .get_matches();
 
@@ -142,6 +679,7 @@ fn main() {
 
         // Error handling
         let verbose = cflag!("verbose");
+        // Panics are caught by compile_many for the file that caused them and by main for anything else, so this only prints
         panic::set_hook(Box::new(move |info| {
             eprintln!("\x1b[91;1merror\x1b[0m: {}", panic_message::panic_info_message(info));
             
@@ -150,84 +688,571 @@ fn main() {
             } else {
                 eprintln!("rerun with -V for verbose error messages");
             }
-            exit(1);
         }));
 
-        // Read input
-        let maybe_filename: Option<String> = carg!("INPUT").filter(|v| *v != "-").or_else(|| carg!("name")).map(|v| String::from(v));
-
-        let text = match carg!("INPUT") {
-            Some("-") | None => {
-                let stdin = io::stdin();
-                let mut stdin = stdin.lock();
-                let mut line = String::new();
-                stdin.read_to_string(&mut line).or_panic();
-                String::from(line)
-            },
-            Some(value) => {
-                fs::read_to_string(value).or_panic()
+        if cflag!("server") {
+            #[cfg(feature = "server")]
+            {
+                run_server();
+                return;
             }
-        };
+            #[cfg(not(feature = "server"))]
+            fail(Error::new(Stage::Config, Location::default(), "server mode requires the server feature"));
+        }
+
+        // Otherwise the output would silently not be type checked
+        #[cfg(not(feature = "compile"))]
+        if cflag!("type-check") {
+            fail(Error::new(Stage::Config, Location::default(), "type checking requires the compile feature"));
+        }
 
-        let mut options = Options {
-            target: String::from(carg!("target").unwrap()),
-            module: cflag!("module"),
-            transpile: true, // !cflag!("preserve")
+        // Resolves the options for an input from the command line, tsconfig.json, mtsc.toml, and its file name
+        // Also returns the config files that were read so that they can be watched
+        let resolve_options = |maybe_filename: &Option<String>| -> Result<(Options, Vec<PathBuf>), Error> {
+            let mut options = Options {
+                filename: maybe_filename.clone(),
+                diagnostic_sink: Some(DiagnosticSink::new(print_diagnostic)),
+                source_map: match carg!("source-map") {
+                    Some("inline") => SourceMapMode::Inline,
+                    Some("external") => SourceMapMode::External,
+                    _ => SourceMapMode::None
+                },
+                target: String::from(carg!("target").unwrap()),
+                module: cflag!("module"),
+                module_kind: carg!("module-kind").and_then(|s| s.parse().ok()).unwrap_or_default(),
+                #[cfg(all(feature = "transpile", not(feature = "compile")))]
+                transpile: true, // !cflag!("preserve")
+                #[cfg(all(feature = "transpile", feature = "compile"))]
+                ts: if cflag!("type-check") {TSMode::Compile} else {TSMode::Transpile},
+                #[cfg(all(not(feature = "transpile"), feature = "compile"))]
+                compile: true,
 
-            use_jsx: cflag!("jsx"),
-            jsx_factory: carg!("jsx").filter(|s| *s != "").map(|s| String::from(s)),
-            jsx_fragment: if carg!("jsx").is_some_and(|s| s != "") {carg!("jsx-factory").map(|s| String::from(s))} else {None},
+                fail_on_error: cflag!("fail-on-error"),
+                declaration: cflag!("declaration"),
+                use_jsx: cflag!("jsx") || cflag!("jsx-mode"),
+                // Unset, a factory from --jsx means 'react' (See Options::resolved_jsx_mode)
+                jsx_mode: carg!("jsx-mode").map(|mode| mode.parse::<JsxMode>().unwrap_or_default()),
+                jsx_factory: carg!("jsx").filter(|s| *s != "").map(|s| String::from(s)),
+                jsx_fragment: carg!("jsx-fragment").map(|s| String::from(s)),
+                jsx_import_source: carg!("jsx-import-source").map(|s| String::from(s)),
+                emit: EmitOptions {
+                    experimental_decorators: cflag!("experimental-decorators"),
+                    emit_decorator_metadata: cflag!("emit-decorator-metadata"),
+                    use_define_for_class_fields: carg!("use-define-for-class-fields").map(|s| s == "true"),
+                    import_helpers: cflag!("import-helpers"),
+                    es_module_interop: cflag!("es-module-interop"),
+                    downlevel_iteration: cflag!("downlevel-iteration"),
+                    verbatim_module_syntax: cflag!("verbatim-module-syntax"),
+                    remove_comments: cflag!("remove-comments"),
+                },
+                #[cfg(feature = "compile")]
+                type_check_options: Default::default(),
             
-            minify: cflag!("minify"),
-            html: cflag!("html"),
+                minify: cflag!("minify"),
+                minify_options: {
+                    let unsafe_transforms = cstrings!("unsafe");
+                    let is_unsafe = |name: &str| unsafe_transforms.iter().any(|transform| transform == name || transform == "all");
+
+                    MinifyOptions {
+                        compress: !cflag!("no-compress"),
+                        mangle: !cflag!("no-mangle"),
+                        drop_console: cflag!("drop-console"),
+                        drop_debugger: !cflag!("keep-debugger"),
+                        pure_funcs: cstrings!("pure-funcs"),
+                        passes: carg!("passes").map_or(1, |s| s.parse().unwrap()),
+                        global_defs: cstrings!("define-global").into_iter().map(|definition| match definition.split_once('=') {
+                            Some((name, value)) => (String::from(name), String::from(value)),
+                            None => (definition, String::from("true"))
+                        }).collect(),
+                        unsafe_transforms: is_unsafe("unsafe"),
+                        unsafe_arrows: is_unsafe("arrows"),
+                        unsafe_comps: is_unsafe("comps"),
+                        unsafe_function: is_unsafe("function"),
+                        unsafe_math: is_unsafe("math"),
+                        unsafe_methods: is_unsafe("methods"),
+                        unsafe_proto: is_unsafe("proto"),
+                        unsafe_regexp: is_unsafe("regexp"),
+                        unsafe_undefined: is_unsafe("undefined"),
+                        reserved: cstrings!("reserved"),
+                        mangle_properties: cflag!("mangle-props"),
+                        mangle_properties_regex: carg!("mangle-props-regex").map(String::from),
+                        mangle_properties_reserved: cstrings!("mangle-props-reserved"),
+                        keep_quoted: cflag!("keep-quoted"),
+                        name_cache: cflag!("name-cache"),
+                        toplevel: cflag!("toplevel"),
+                        keep_fnames: cflag!("keep-fnames"),
+                        comments: carg!("comments").and_then(|s| s.parse::<CommentMode>().ok()).unwrap_or_default(),
+                        extract_licenses: cflag!("extract-licenses"),
+                        ..Default::default()
+                    }
+                },
+                html: cflag!("html"),
 
-            preprocess: cflag!("preprocess"),
-            macros: cstrings!("define"),
-            filename: maybe_filename.clone(),
-            include_paths: cstrings!("include-paths"),
+                preprocess: cflag!("preprocess"),
+                macros: cstrings!("define"),
+                include_paths: cstrings!("include-paths"),
+
+                banner: carg!("banner").map(String::from),
+                footer: carg!("footer").map(String::from),
+            };
+
+            // Read tsconfig.json and then mtsc.toml
+            let cli_options = options.clone();
+            let tsconfig_path = if cflag!("no-tsconfig") {
+                None
+            } else {
+                carg!("tsconfig").map(PathBuf::from).or_else(|| mtsc::util::find_tsconfig(Path::new(maybe_filename.as_deref().unwrap_or("."))))
+            };
+
+            if let Some(ref tsconfig_path) = tsconfig_path {
+                mtsc::util::update_options_from_tsconfig(tsconfig_path, &mut options)?;
+            }
+
+            #[cfg(feature = "config")]
+            let config_path = if cflag!("no-config") {
+                None
+            } else {
+                carg!("config").map(PathBuf::from).or_else(|| mtsc::util::find_config(Path::new(maybe_filename.as_deref().unwrap_or("."))))
+            };
+            #[cfg(not(feature = "config"))]
+            let config_path: Option<PathBuf> = None;
+
+            #[cfg(feature = "config")]
+            if let Some(ref config_path) = config_path {
+                mtsc::util::update_options_from_config(config_path, maybe_filename.as_deref().map(Path::new), &mut options)?;
+            }
+
+            // Options from the command line take precedence over both
+            macro_rules! prefer_flags {
+                ($($flag:literal => $($field:ident).+;)*) => {
+                    $(
+                        if cflag!($flag) {
+                            options.$($field).+ = cli_options.$($field).+.clone();
+                        }
+                    )*
+                }
+            }
+
+            prefer_flags! {
+                "target" => target;
+                "module" => module;
+                "module-kind" => module_kind;
+                "jsx" => use_jsx;
+                "jsx" => jsx_mode;
+                "jsx" => jsx_factory;
+                "jsx" => jsx_fragment;
+                "jsx-fragment" => jsx_fragment;
+                "jsx-mode" => use_jsx;
+                "jsx-mode" => jsx_mode;
+                "jsx-import-source" => jsx_import_source;
+                "source-map" => source_map;
+                "declaration" => declaration;
+                "fail-on-error" => fail_on_error;
+                "experimental-decorators" => emit.experimental_decorators;
+                "emit-decorator-metadata" => emit.emit_decorator_metadata;
+                "use-define-for-class-fields" => emit.use_define_for_class_fields;
+                "import-helpers" => emit.import_helpers;
+                "es-module-interop" => emit.es_module_interop;
+                "downlevel-iteration" => emit.downlevel_iteration;
+                "verbatim-module-syntax" => emit.verbatim_module_syntax;
+                "remove-comments" => emit.remove_comments;
+                "minify" => minify;
+                "no-compress" => minify_options.compress;
+                "no-mangle" => minify_options.mangle;
+                "drop-console" => minify_options.drop_console;
+                "keep-debugger" => minify_options.drop_debugger;
+                "passes" => minify_options.passes;
+                "toplevel" => minify_options.toplevel;
+                "keep-fnames" => minify_options.keep_fnames;
+                "mangle-props" => minify_options.mangle_properties;
+                "mangle-props-regex" => minify_options.mangle_properties_regex;
+                "keep-quoted" => minify_options.keep_quoted;
+                "name-cache" => minify_options.name_cache;
+                "comments" => minify_options.comments;
+                "extract-licenses" => minify_options.extract_licenses;
+                "html" => html;
+                "preprocess" => preprocess;
+                "banner" => banner;
+                "footer" => footer;
+            }
+
+            #[cfg(all(feature = "transpile", feature = "compile"))]
+            if cflag!("type-check") {
+                options.ts = cli_options.ts;
+            }
+
+            // Lists like macros and include paths from the command line are added to those from the config
+            macro_rules! extend_flags {
+                ($($flag:literal => $($field:ident).+;)*) => {
+                    $(
+                        if cflag!($flag) {
+                            options.$($field).+.retain(|value| !cli_options.$($field).+.contains(value));
+                            options.$($field).+.extend(cli_options.$($field).+.iter().cloned());
+                        }
+                    )*
+                }
+            }
+
+            extend_flags! {
+                "define" => macros;
+                "include-paths" => include_paths;
+                "pure-funcs" => minify_options.pure_funcs;
+                "reserved" => minify_options.reserved;
+                "mangle-props-reserved" => minify_options.mangle_properties_reserved;
+            }
+
+            // Global definitions from the command line replace those from the config with the same name
+            if cflag!("define-global") {
+                options.minify_options.global_defs.extend(cli_options.minify_options.global_defs.clone());
+            }
+
+            // Unsafe transforms from the command line are enabled on top of those from the config
+            if cflag!("unsafe") {
+                let minify_options = &mut options.minify_options;
+                let cli_minify_options = &cli_options.minify_options;
+                minify_options.unsafe_transforms |= cli_minify_options.unsafe_transforms;
+                minify_options.unsafe_arrows |= cli_minify_options.unsafe_arrows;
+                minify_options.unsafe_comps |= cli_minify_options.unsafe_comps;
+                minify_options.unsafe_function |= cli_minify_options.unsafe_function;
+                minify_options.unsafe_math |= cli_minify_options.unsafe_math;
+                minify_options.unsafe_methods |= cli_minify_options.unsafe_methods;
+                minify_options.unsafe_proto |= cli_minify_options.unsafe_proto;
+                minify_options.unsafe_regexp |= cli_minify_options.unsafe_regexp;
+                minify_options.unsafe_undefined |= cli_minify_options.unsafe_undefined;
+            }
+
+            let dependencies: Vec<PathBuf> = tsconfig_path.into_iter().chain(config_path).collect();
+
+            if let Some(ref filename) = maybe_filename {
+                mtsc::util::update_options(mtsc::util::OptionSource::Path(PathBuf::from(filename)), &mut options, &Options {
+                    #[cfg(all(feature = "transpile", not(feature = "compile")))]
+                    transpile: true,// !cflag!("preserve"),
+                    #[cfg(all(feature = "transpile", feature = "compile"))]
+                    ts: if cflag!("type-check") {TSMode::Compile} else {TSMode::Transpile},
+                    ..mtsc::util::all_options()
+                });
+            }
+
+            return Ok((options, dependencies));
         };
 
-        if let Some(ref filename) = maybe_filename {
-            mtsc::util::update_options(mtsc::util::OptionSource::Path(PathBuf::from(filename)), &mut options, &Options {
-                transpile: true,// !cflag!("preserve"),
-                ..mtsc::util::all_options()
-            });
+        // Print the options a file would be compiled with instead of compiling anything
+        #[cfg(feature = "config")]
+        if let Some(filename) = carg!("print-config") {
+            let (options, dependencies) = resolve_options(&Some(String::from(filename))).unwrap_or_else(|e| fail(e));
+
+            for dependency in dependencies {
+                eprintln!("\x1b[96;1mmessage\x1b[0m: read options from {}", dependency.display());
+            }
+            print!("{}", toml::to_string_pretty(&options).unwrap_or_else(|e| fail(Error::new(Stage::Config, Location::file(Some(String::from(filename))), e.to_string()))));
+            return;
+        }
+        #[cfg(not(feature = "config"))]
+        for flag in ["print-config", "config"].iter() {
+            if cflag!(*flag) {
+                fail(Error::new(Stage::Config, Location::default(), format!("--{} requires the config feature", flag)));
+            }
         }
 
-        // Compile
-        let result = compile(text, &options).unwrap();
-        
-        // Write output
-        match carg!("output") {
-            Some("-") | Some("") if cflag!("output") => print!("{}",result),
-            None | Some("") => {
-                match maybe_filename {
-                    Some(ref filename) => {
-                        let mut path = PathBuf::from(filename);
-                        mtsc::util::update_path(&mut path,&options);
+        // Collect inputs
+        let inputs = collect_inputs(matches.values_of("INPUT").map_or_else(|| vec!["-"], |values| values.collect())).unwrap_or_else(|e| fail(e));
+        let output_directory = carg!("outdir").map(PathBuf::from);
 
-                        if maybe_filename.is_some() && is_same_file(path.as_path(),maybe_filename.as_ref().unwrap()).unwrap_or_default() {
-                            panic!("Output file is the same as the input");
-                        }
+        if inputs.is_empty() {
+            fail(Error::new(Stage::Io, Location::default(), "no input files found"));
+        } else if inputs.len() > 1 && cflag!("output") {
+            fail(Error::new(Stage::Io, Location::default(), "the out option only supports a single input (use outdir instead)"));
+        }
 
-                        fs::write(path,result.as_bytes()).or_panic();
-                    },
-                    None => print!("{}",result)
+        // Reads an input and resolves its options and output path
+        let prepare_input = |input: &Input| -> Result<(Job, String), Error> {
+            // Read input
+            let maybe_filename: Option<String> = match input.path {
+                Some(ref path) => Some(path.to_string_lossy().into_owned()),
+                None => carg!("name").map(|v| String::from(v))
+            };
+
+            let text = match input.path {
+                None => {
+                    let stdin = io::stdin();
+                    let mut stdin = stdin.lock();
+                    let mut line = String::new();
+                    stdin.read_to_string(&mut line).map_err(|e| Error::io(None::<String>, e))?;
+                    String::from(line)
+                },
+                Some(ref path) => {
+                    fs::read_to_string(path).map_err(|e| Error::io(path.to_str(), e))?
                 }
-            },
-            Some(value) => {
-                let mut path = PathBuf::from(value);
+            };
+
+            let (mut options, dependencies) = resolve_options(&maybe_filename)?;
 
-                if path.is_dir() {
-                    path.push(maybe_filename.as_ref().unwrap_or(&String::from("out.js")));
+            // Resolve output path
+            let output_path: Option<PathBuf> = match (carg!("output"), &output_directory) {
+                (_, Some(output_directory)) if maybe_filename.is_some() => {
+                    let path = PathBuf::from(maybe_filename.as_ref().unwrap());
+                    let mut output_path = output_directory.join(path.strip_prefix(&input.root).ok().or_else(|| path.file_name().map(Path::new)).unwrap_or(&path));
+                    mtsc::util::update_path(&mut output_path,&options);
+
+                    if let Some(parent) = output_path.parent() {
+                        fs::create_dir_all(parent).map_err(|e| Error::io(parent.to_str(), e))?;
+                    }
+                    Some(output_path)
+                },
+                (Some("-"), _) | (Some(""), _) if cflag!("output") => None,
+                (None, _) | (Some(""), _) => maybe_filename.as_ref().map(|filename| {
+                    let mut path = PathBuf::from(filename);
                     mtsc::util::update_path(&mut path,&options);
+                    path
+                }),
+                (Some(value), _) => {
+                    let mut path = PathBuf::from(value);
+
+                    if path.is_dir() {
+                        path.push(maybe_filename.as_ref().unwrap_or(&String::from("out.js")));
+                        mtsc::util::update_path(&mut path,&options);
+                    }
+
+                    Some(path)
                 }
+            };
+
+            // Declaration files only produce a declaration, which is pointless to write to stdout or over the input
+            let is_declaration_input = maybe_filename.as_ref().is_some_and(|filename| mtsc::util::is_declaration_path(PathBuf::from(filename).as_path()));
+            let skip = is_declaration_input && (!options.declaration || output_path.as_ref().map_or(true, |path| is_same_file(path.as_path(),maybe_filename.as_ref().unwrap()).unwrap_or_default()));
 
+            if skip {
+                eprintln!("\x1b[96;1mmessage\x1b[0m: skipping declaration file {}", maybe_filename.as_ref().unwrap());
+            } else if let Some(ref path) = output_path {
                 if maybe_filename.is_some() && is_same_file(path.as_path(),maybe_filename.as_ref().unwrap()).unwrap_or_default() {
-                    panic!("Output file is the same as the input");
+                    return Err(Error::new(Stage::Io, Location::file(maybe_filename.clone()), "output file is the same as the input"));
+                }
+            } else if options.source_map == SourceMapMode::External {
+                // There is no file to put a map next to when writing to stdout
+                options.source_map = SourceMapMode::Inline;
+            }
+
+            return Ok((Job { options, maybe_filename, output_path, is_declaration_input, skip, dependencies }, text));
+        };
+
+        // Writes the output of a job and returns any other files it was built from so that they can be watched
+        let write_output = |job: Job, output: Output| -> Result<Vec<PathBuf>, Error> {
+            let Job { options, maybe_filename, output_path, is_declaration_input, mut dependencies, .. } = job;
+            dependencies.extend(output.dependencies.iter().map(PathBuf::from));
+
+            if is_declaration_input {
+                let mut path = output_path.unwrap();
+                mtsc::util::update_declaration_path(&mut path);
+                fs::write(&path,output.declaration.unwrap_or_default().as_bytes()).map_err(|e| Error::io(path.to_str(), e))?;
+                return Ok(dependencies);
+            }
+
+            // Write declaration
+            if let Some(ref declaration) = output.declaration {
+                match output_path.clone().or_else(|| maybe_filename.as_ref().map(|filename| {
+                    let mut path = PathBuf::from(filename);
+                    mtsc::util::update_path(&mut path,&options);
+                    path
+                })) {
+                    Some(mut path) => {
+                        mtsc::util::update_declaration_path(&mut path);
+                        fs::write(&path,declaration.as_bytes()).map_err(|e| Error::io(path.to_str(), e))?;
+                    },
+                    None => eprintln!("\x1b[93;1mwarning\x1b[0m: no declaration file written since there is no output or input file name")
                 }
+            }
+        
+            // Write output
+            match output_path {
+                Some(path) => {
+                    let mut code = output.code;
+
+                    if let (SourceMapMode::External, Some(source_map)) = (options.source_map, output.source_map) {
+                        let mut map_path = path.clone().into_os_string();
+                        map_path.push(".map");
+                        let map_path = PathBuf::from(map_path);
+
+                        if !code.ends_with('\n') {
+                            code.push('\n');
+                        }
+                        code.push_str(&format!("//# sourceMappingURL={}\n", map_path.file_name().unwrap_or_default().to_string_lossy()));
+
+                        fs::write(&map_path,source_map.as_bytes()).map_err(|e| Error::io(map_path.to_str(), e))?;
+                    }
+
+                    if let Some(ref licenses) = output.licenses {
+                        let mut licenses_path = path.clone().into_os_string();
+                        licenses_path.push(".LICENSE.txt");
+                        let licenses_path = PathBuf::from(licenses_path);
+                        fs::write(&licenses_path,licenses.as_bytes()).map_err(|e| Error::io(licenses_path.to_str(), e))?;
+                    }
 
-                fs::write(path,result.as_bytes()).or_panic();
+                    fs::write(&path,code.as_bytes()).map_err(|e| Error::io(path.to_str(), e))?;
+                },
+                None => {
+                    if output.licenses.is_some() {
+                        eprintln!("\x1b[93;1mwarning\x1b[0m: no license file written since there is no output file name");
+                    }
+                    print!("{}",output.code)
+                }
+            }
+
+            return Ok(dependencies);
+        };
+
+        // Compiles inputs across the worker pool and writes their outputs in order
+        let jobs: usize = carg!("jobs").map_or(1, |s| s.parse().unwrap());
+        let cache = carg!("cache-dir").map(mtsc::Cache::new);
+
+        // The name cache is loaded once and saved after every batch so that each build starts from the names of the last
+        let name_cache_path = carg!("name-cache").map(PathBuf::from);
+        if let Some(ref path) = name_cache_path {
+            if path.exists() {
+                mtsc::set_name_cache(Some(fs::read_to_string(path).unwrap_or_else(|e| fail(Error::io(path.to_str(), e)))));
             }
         }
-}
+
+        // Each result has the files an input was built from, which are known for most failures as well so that fixing any of them triggers a rebuild
+        let build = |inputs: &[&Input]| -> Vec<Result<Vec<PathBuf>, (Error, Vec<PathBuf>)>> {
+            let prepared: Vec<Result<(Job, String), Error>> = inputs.iter().map(|input| prepare_input(input)).collect();
+
+            // Cached outputs are used as is, so only the rest are compiled
+            let cached: Vec<Option<Output>> = prepared.iter().map(|result| match (result, &cache) {
+                (Ok((job, text)), Some(cache)) if !job.skip => cache.get(text, &job.options),
+                _ => None
+            }).collect();
+
+            let batch: Vec<(&String, Options)> = prepared.iter().zip(cached.iter())
+                .filter_map(|(result, cached)| result.as_ref().ok().filter(|(job, _)| !job.skip && cached.is_none()))
+                .map(|(job, text)| (text, job.options.clone()))
+                .collect();
+            let mut outputs = mtsc::compile_many(&batch, jobs).into_iter();
+
+            let results: Vec<Result<Vec<PathBuf>, (Error, Vec<PathBuf>)>> = prepared.into_iter().zip(cached).map(|(result, cached)| {
+                let (job, text) = result.map_err(|error| (error, vec![]))?;
+                if job.skip {
+                    return Ok(job.dependencies);
+                }
+
+                let output = match cached {
+                    Some(output) => output,
+                    None => {
+                        let output = match outputs.next().unwrap() {
+                            (Ok(output), _) => output,
+                            (Err(error), dependencies) => {
+                                let mut all_dependencies = job.dependencies;
+                                all_dependencies.extend(dependencies.into_iter().map(PathBuf::from));
+                                return Err((error, all_dependencies));
+                            }
+                        };
+                        if let Some(ref cache) = cache {
+                            if let Err(e) = cache.put(&text, &job.options, &output) {
+                                eprintln!("\x1b[93;1mwarning\x1b[0m: failed to write to cache {}: {}", cache.directory().display(), e);
+                            }
+                        }
+                        output
+                    }
+                };
+                let dependencies: Vec<PathBuf> = job.dependencies.iter().cloned().chain(output.dependencies.iter().map(PathBuf::from)).collect();
+                write_output(job, output).map_err(|error| (error, dependencies))
+            }).collect();
+
+            if let (Some(path), Some(name_cache)) = (&name_cache_path, mtsc::name_cache()) {
+                if let Err(e) = fs::write(path, name_cache) {
+                    report_error(&Error::io(path.to_str(), e));
+                }
+            }
+
+            return results;
+        };
+
+        // Compile every input, reporting errors as they happen so that one bad file does not stop the rest
+        let mut first_error: Option<Error> = None;
+        let mut watched: HashMap<PathBuf, Vec<(PathBuf, Option<SystemTime>)>> = HashMap::new();
+
+        // Snapshots the modification times of an input and everything it depends on
+        let snapshot = |path: &Path, dependencies: &[PathBuf]| -> Vec<(PathBuf, Option<SystemTime>)> {
+            return std::iter::once(path).chain(dependencies.iter().map(PathBuf::as_path))
+                .map(|path| (path.to_path_buf(), fs::metadata(path).and_then(|metadata| metadata.modified()).ok()))
+                .collect();
+        };
+
+        for (input, result) in inputs.iter().zip(build(&inputs.iter().collect::<Vec<_>>())) {
+            if let Some(ref path) = input.path {
+                let dependencies = match result {
+                    Ok(ref dependencies) | Err((_, ref dependencies)) => dependencies
+                };
+                watched.insert(path.clone(), snapshot(path, dependencies));
+            }
+
+            if let Err((error, _)) = result {
+                report_error(&error);
+                first_error.get_or_insert(error);
+            }
+        }
+
+        if !cflag!("watch") {
+            if let Some(error) = first_error {
+                exit(exit_code(&error));
+            }
+            return;
+        }
+
+        // Watch for changes by polling modification times, which keeps the loaded runtime warm between rebuilds
+        if inputs.iter().any(|input| input.path.is_none()) {
+            fail(Error::new(Stage::Io, Location::default(), "stdin cannot be watched"));
+        }
+
+        eprintln!("\x1b[96;1mwatching\x1b[0m {} file(s) for changes", watched.len());
+        loop {
+            thread::sleep(Duration::from_millis(250));
+
+            // Inputs are collected again so that new files in watched directories and glob patterns are picked up
+            let inputs = collect_inputs(matches.values_of("INPUT").map_or_else(Vec::new, |values| values.collect())).unwrap_or_default();
+
+            let changed_inputs: Vec<&Input> = inputs.iter().filter(|input| match watched.get(input.path.as_ref().unwrap()) {
+                Some(files) => files.iter().any(|(file, modified)| fs::metadata(file).and_then(|metadata| metadata.modified()).ok() != *modified),
+                None => true
+            }).collect();
+
+            if changed_inputs.is_empty() {
+                continue;
+            }
+
+            // Errors are only reported once per build, so ones from earlier builds no longer matter
+            if let Ok(mut reported_errors) = REPORTED_ERRORS.lock() {
+                reported_errors.clear();
+            }
+
+            let start = Instant::now();
+            let mut rebuilt: Vec<String> = vec![];
+            let mut failed: Vec<String> = vec![];
+
+            for (input, result) in changed_inputs.iter().zip(build(&changed_inputs)) {
+                let path = input.path.clone().unwrap();
+                match result {
+                    Ok(dependencies) => {
+                        watched.insert(path.clone(), snapshot(&path, &dependencies));
+                        rebuilt.push(path.display().to_string());
+                    },
+                    Err((error, mut dependencies)) => {
+                        // Keep watching the files from the last build as well so that fixing any of them triggers another rebuild
+                        for (file, _) in watched.get(&path).into_iter().flatten().skip(1) {
+                            if !dependencies.contains(file) {
+                                dependencies.push(file.clone());
+                            }
+                        }
+                        watched.insert(path.clone(), snapshot(&path, &dependencies));
+                        report_error(&error);
+                        failed.push(path.display().to_string());
+                    }
+                }
+            }
+
+            match (rebuilt.is_empty(), failed.is_empty()) {
+                (_, true) => eprintln!("\x1b[92;1mrebuilt\x1b[0m {} in {}ms", rebuilt.join(", "), start.elapsed().as_millis()),
+                (true, false) => eprintln!("\x1b[91;1mfailed\x1b[0m {} in {}ms", failed.join(", "), start.elapsed().as_millis()),
+                (false, false) => eprintln!("\x1b[93;1mrebuilt\x1b[0m {} in {}ms (\x1b[91;1mfailed\x1b[0m {})", rebuilt.join(", "), start.elapsed().as_millis(), failed.join(", "))
+            }
+        }
+}
\ No newline at end of file
@@ -0,0 +1,53 @@
+// Worker Pool
+// Threads are kept for the life of the process so that the runtime each one loads TypeScript and Terser into stays warm between calls to compile_many
+// The pool only grows, up to the largest number of jobs asked for so far
+use std::panic::{self,AssertUnwindSafe};
+use std::sync::{Arc,Mutex,PoisonError};
+use std::sync::mpsc::{self,Receiver,Sender};
+use std::thread;
+
+type Task = Box<dyn FnOnce() + Send>;
+
+struct Pool {
+    sender: Sender<Task>,
+    receiver: Arc<Mutex<Receiver<Task>>>,
+    workers: usize,
+}
+
+static POOL: Mutex<Option<Pool>> = Mutex::new(None);
+
+fn work(receiver: Arc<Mutex<Receiver<Task>>>) {
+    loop {
+        // The lock is released before running the task so that other workers can take the next one
+        let task = match receiver.lock().unwrap_or_else(PoisonError::into_inner).recv() {
+            Ok(task) => task,
+            Err(_) => return
+        };
+
+        // Tasks catch their own panics (See compile_many), so this only keeps the worker alive if one gets through
+        let _ = panic::catch_unwind(AssertUnwindSafe(task));
+    }
+}
+
+// Runs each task on a pool thread, starting more threads if there are fewer than workers
+pub(crate) fn run(workers: usize, tasks: Vec<Task>) {
+    let mut pool = POOL.lock().unwrap_or_else(PoisonError::into_inner);
+    let pool = pool.get_or_insert_with(|| {
+        let (sender, receiver) = mpsc::channel();
+        Pool { sender, receiver: Arc::new(Mutex::new(receiver)), workers: 0 }
+    });
+
+    while pool.workers < workers {
+        let receiver = pool.receiver.clone();
+        thread::Builder::new()
+            .name(format!("mtsc-worker-{}", pool.workers))
+            .spawn(move || work(receiver))
+            .expect("failed to start a worker thread");
+        pool.workers += 1;
+    }
+
+    for task in tasks {
+        // Workers never stop while the pool holds the receiver, so sending cannot fail
+        let _ = pool.sender.send(task);
+    }
+}
@@ -0,0 +1,329 @@
+// Source Maps
+// See https://sourcemaps.info/spec.html
+
+use crate::error::Location;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub struct OriginalPosition {
+    pub source: u32,
+    pub line: u32,
+    pub column: u32,
+    pub name: Option<u32>,
+}
+
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub struct Segment {
+    pub column: u32,
+    pub original: Option<OriginalPosition>,
+}
+
+// Lines and columns are zero-based
+#[derive(Clone,Default,Debug,PartialEq,Eq)]
+pub struct SourceMap {
+    pub file: Option<String>,
+    pub sources: Vec<String>,
+    pub sources_content: Vec<Option<String>>,
+    pub names: Vec<String>,
+    pub mappings: Vec<Vec<Segment>>,
+}
+
+impl SourceMap {
+    pub fn new(file: Option<String>, sources: Vec<String>, names: Vec<String>, mappings: &str) -> Option<Self> {
+        return Some(SourceMap {
+            file,
+            sources,
+            sources_content: vec![],
+            names,
+            mappings: decode_mappings(mappings)?,
+        });
+    }
+
+    // Finds the closest mapping at or before the given position
+    // Positions past the start of a segment are assumed to be offset by the same amount in the original
+    pub fn lookup(&self, line: u32, column: u32) -> Option<OriginalPosition> {
+        let segment = self.mappings.get(line as usize)?.iter()
+            .take_while(|segment| segment.column <= column)
+            .last()?;
+
+        return segment.original.map(|original| OriginalPosition {
+            column: original.column + (column - segment.column),
+            ..original
+        });
+    }
+
+    // Given self (output -> intermediate) and inner (intermediate -> original), produces output -> original
+    pub fn compose(&self, inner: &SourceMap) -> SourceMap {
+        let mut names: Vec<String> = inner.names.clone();
+        let mut get_name_index = |name: &str| -> u32 {
+            match names.iter().position(|it| it == name) {
+                Some(i) => i as u32,
+                None => {
+                    names.push(String::from(name));
+                    (names.len() - 1) as u32
+                }
+            }
+        };
+
+        let mappings = self.mappings.iter().map(|line| {
+            line.iter().filter_map(|segment| {
+                let original = match segment.original {
+                    Some(original) => original,
+                    None => return Some(*segment)
+                };
+
+                let mut mapped = inner.lookup(original.line, original.column)?;
+
+                if mapped.name.is_none() {
+                    mapped.name = original.name.and_then(|name| self.names.get(name as usize)).map(|name| get_name_index(name));
+                }
+
+                Some(Segment { column: segment.column, original: Some(mapped) })
+            }).collect()
+        }).collect();
+
+        return SourceMap {
+            file: self.file.clone(),
+            sources: inner.sources.clone(),
+            sources_content: inner.sources_content.clone(),
+            names,
+            mappings,
+        };
+    }
+
+    pub fn encode_mappings(&self) -> String {
+        let mut result = String::new();
+        let (mut previous_source, mut previous_line, mut previous_column, mut previous_name) = (0i64, 0i64, 0i64, 0i64);
+
+        for (i, line) in self.mappings.iter().enumerate() {
+            if i > 0 {
+                result.push(';');
+            }
+
+            let mut previous_generated_column = 0i64;
+            for (j, segment) in line.iter().enumerate() {
+                if j > 0 {
+                    result.push(',');
+                }
+
+                encode_vlq(&mut result, segment.column as i64 - previous_generated_column);
+                previous_generated_column = segment.column as i64;
+
+                if let Some(original) = segment.original {
+                    encode_vlq(&mut result, original.source as i64 - previous_source);
+                    encode_vlq(&mut result, original.line as i64 - previous_line);
+                    encode_vlq(&mut result, original.column as i64 - previous_column);
+                    previous_source = original.source as i64;
+                    previous_line = original.line as i64;
+                    previous_column = original.column as i64;
+
+                    if let Some(name) = original.name {
+                        encode_vlq(&mut result, name as i64 - previous_name);
+                        previous_name = name as i64;
+                    }
+                }
+            }
+        }
+
+        return result;
+    }
+
+    pub fn to_json(&self) -> String {
+        fn string_array<'a, I: Iterator<Item = Option<&'a str>>>(values: I) -> String {
+            format!("[{}]", values.map(|value| value.map_or_else(|| String::from("null"), json_string)).collect::<Vec<String>>().join(","))
+        }
+
+        let mut result = String::from(r#"{"version":3"#);
+
+        if let Some(ref file) = self.file {
+            result.push_str(&format!(r#","file":{}"#, json_string(file)));
+        }
+
+        result.push_str(&format!(r#","sources":{}"#, string_array(self.sources.iter().map(|source| Some(source.as_str())))));
+
+        if self.sources_content.iter().any(Option::is_some) {
+            result.push_str(&format!(r#","sourcesContent":{}"#, string_array(self.sources_content.iter().map(Option::as_deref))));
+        }
+
+        result.push_str(&format!(r#","names":{}"#, string_array(self.names.iter().map(|name| Some(name.as_str())))));
+        result.push_str(&format!(r#","mappings":{}}}"#, json_string(&self.encode_mappings())));
+
+        return result;
+    }
+
+    // Points a (one-based) location in the generated code back to where it came from
+    pub fn remap_location(&self, location: &mut Location) {
+        let line = match location.line {
+            Some(line) if line > 0 => line - 1,
+            _ => return
+        };
+        let column = location.column.unwrap_or(1).max(1) - 1;
+
+        if let Some(original) = self.lookup(line as u32, column as u32) {
+            if let Some(source) = self.sources.get(original.source as usize) {
+                location.filename = Some(source.clone()).filter(|source| source != "<stdin>");
+            }
+            location.line = Some(original.line as usize + 1);
+            location.column = location.column.map(|_| original.column as usize + 1);
+        }
+    }
+
+    pub fn to_data_url(&self) -> String {
+        format!("data:application/json;charset=utf-8;base64,{}", base64(self.to_json().as_bytes()))
+    }
+}
+
+pub fn decode_mappings(mappings: &str) -> Option<Vec<Vec<Segment>>> {
+    let (mut source, mut line, mut column, mut name) = (0i64, 0i64, 0i64, 0i64);
+
+    return mappings.split(';').map(|text| {
+        let mut generated_column = 0i64;
+        text.split(',').filter(|segment| !segment.is_empty()).map(|segment| {
+            let values = decode_vlq(segment)?;
+            generated_column += *values.first()?;
+
+            let original = if values.len() >= 4 {
+                source += values[1];
+                line += values[2];
+                column += values[3];
+
+                let name_index = values.get(4).map(|value| {
+                    name += value;
+                    name as u32
+                });
+
+                Some(OriginalPosition { source: source as u32, line: line as u32, column: column as u32, name: name_index })
+            } else {
+                None
+            };
+
+            Some(Segment { column: generated_column as u32, original })
+        }).collect::<Option<Vec<Segment>>>()
+    }).collect();
+}
+
+fn decode_vlq(segment: &str) -> Option<Vec<i64>> {
+    let mut values = vec![];
+    let (mut value, mut shift) = (0i64, 0);
+
+    for byte in segment.bytes() {
+        let digit = BASE64.iter().position(|it| *it == byte)? as i64;
+        value += (digit & 0b11111) << shift;
+
+        if digit & 0b100000 == 0 {
+            values.push(if value & 1 == 1 { -(value >> 1) } else { value >> 1 });
+            value = 0;
+            shift = 0;
+        } else {
+            shift += 5;
+        }
+    }
+
+    return Some(values);
+}
+
+fn encode_vlq(result: &mut String, value: i64) {
+    let mut value = if value < 0 { ((-value) << 1) | 1 } else { value << 1 };
+    loop {
+        let mut digit = value & 0b11111;
+        value >>= 5;
+        if value > 0 {
+            digit |= 0b100000;
+        }
+        result.push(BASE64[digit as usize] as char);
+        if value == 0 {
+            break;
+        }
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    let mut result = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let n = (chunk[0] as u32) << 16 | (*chunk.get(1).unwrap_or(&0) as u32) << 8 | *chunk.get(2).unwrap_or(&0) as u32;
+        result.push(BASE64[(n >> 18) as usize & 63] as char);
+        result.push(BASE64[(n >> 12) as usize & 63] as char);
+        result.push(if chunk.len() > 1 { BASE64[(n >> 6) as usize & 63] as char } else { '=' });
+        result.push(if chunk.len() > 2 { BASE64[n as usize & 63] as char } else { '=' });
+    }
+    return result;
+}
+
+pub(crate) fn json_string<S: AsRef<str>>(value: S) -> String {
+    let mut result = String::from("\"");
+    for char in value.as_ref().chars() {
+        match char {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c)
+        }
+    }
+    result.push('"');
+    return result;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VLQ: &[(i64, &str)] = &[(0, "A"), (1, "C"), (-1, "D"), (15, "e"), (-15, "f"), (16, "gB"), (-16, "hB"), (123, "2H"), (-123, "3H"), (1000, "w+B")];
+
+    #[test]
+    fn vlq() {
+        for (value, text) in VLQ.iter() {
+            let mut result = String::new();
+            encode_vlq(&mut result, *value);
+            assert_eq!(result, *text, "encoding {}", value);
+            assert_eq!(decode_vlq(text), Some(vec![*value]), "decoding {}", text);
+        }
+
+        assert_eq!(decode_vlq("2H3HgB"), Some(vec![123, -123, 16]));
+        assert_eq!(decode_vlq("A!"), None);
+    }
+
+    #[test]
+    fn mappings_round_trip() {
+        let mappings = "AAAA,SAASC;;AACA,gBAAmB;EADHD";
+        let map = SourceMap::new(None, vec![String::from("a.ts")], vec![String::from("a"), String::from("b")], mappings).unwrap();
+
+        assert_eq!(map.mappings[0][1], Segment { column: 9, original: Some(OriginalPosition { source: 0, line: 0, column: 9, name: Some(1) }) });
+        assert!(map.mappings[1].is_empty());
+        assert_eq!(map.mappings[3][0], Segment { column: 2, original: Some(OriginalPosition { source: 0, line: 0, column: 25, name: Some(0) }) });
+        assert_eq!(map.encode_mappings(), mappings);
+    }
+
+    #[test]
+    fn compose() {
+        // intermediate -> original: line 0 starts at line 2, column 4 of the original
+        let inner = SourceMap::new(None, vec![String::from("input.ts")], vec![], "AAEI").unwrap();
+        // output -> intermediate: column 0 is column 0, column 5 is column 3 and named 'foo', and line 1 points past the end of the intermediate
+        let outer = SourceMap::new(Some(String::from("output.js")), vec![String::from("intermediate.js")], vec![String::from("foo")], "AAAA,KAAGA;AAKA").unwrap();
+
+        let composed = outer.compose(&inner);
+        assert_eq!(composed.file.as_deref(), Some("output.js"));
+        assert_eq!(composed.sources, vec![String::from("input.ts")]);
+        assert_eq!(composed.names, vec![String::from("foo")]);
+        assert_eq!(composed.mappings, vec![
+            vec![
+                Segment { column: 0, original: Some(OriginalPosition { source: 0, line: 2, column: 4, name: None }) },
+                Segment { column: 5, original: Some(OriginalPosition { source: 0, line: 2, column: 7, name: Some(0) }) },
+            ],
+            vec![],
+        ]);
+    }
+
+    #[test]
+    fn base64_padding() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"a"), "YQ==");
+        assert_eq!(base64(b"ab"), "YWI=");
+        assert_eq!(base64(b"abc"), "YWJj");
+        assert_eq!(base64(b"abcd"), "YWJjZA==");
+        assert_eq!(base64(&[0xff, 0xfe]), "//4=");
+    }
+}
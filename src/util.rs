@@ -37,8 +37,30 @@ macro_rules! optional {
     }
 }
 
+pub mod glob;
+mod jsonc;
+mod tsconfig;
+pub use tsconfig::{find_tsconfig,update_options_from_tsconfig};
+#[cfg(feature = "config")]
+mod config;
+#[cfg(feature = "config")]
+pub use config::{find_config,update_options_from_config};
+
 use std::path::{Path,PathBuf};
+
+// Finds the nearest file with the given name in the directory of path (or path itself if it is a directory) or any of its parents
+pub(crate) fn find_nearest(path: &Path, name: &str) -> Option<PathBuf> {
+    let directory = if path.is_dir() {path} else {path.parent().unwrap_or(Path::new(""))};
+    let directory = if directory.as_os_str().is_empty() {Path::new(".")} else {directory};
+    let directory = std::fs::canonicalize(directory).unwrap_or_else(|_| directory.to_path_buf());
+
+    return directory.ancestors().map(|directory| directory.join(name)).find(|path| path.is_file());
+}
+
+// Serialized as {"mime": "text/html"}, {"path": "index.html"}, or "none"
 #[derive(PartialEq,Eq,Default,Clone,Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum OptionSource {
     Mime(String),
     Path(PathBuf),
@@ -123,9 +145,30 @@ pub fn update_options<'a>(source: OptionSource, options: &'a mut Options, mask:
     return options;
 }
 
+// Matches '*.d.ts', '*.d.mts', and '*.d.cts' files
+pub fn is_declaration_path(path: &Path) -> bool {
+    return path.file_stem().and_then(|stem| Path::new(stem).extension()).is_some_and(|subext| subext == "d")
+        && path.extension().and_then(|ext| ext.to_str()).is_some_and(|ext| matches!(ext, "ts" | "mts" | "cts"));
+}
+
+// Matches files that are compiled when a directory is given as input, such as '*.ts' or '*.p.js' but not '*.d.ts'
+pub fn is_source_path(path: &Path) -> bool {
+    if is_declaration_path(path) {
+        return false;
+    }
+
+    return path.extension().and_then(|ext| ext.to_str()).is_some_and(|ext| matches!(ext, "ts" | "tsx" | "mts" | "cts"))
+        || path.file_stem().and_then(|stem| Path::new(stem).extension()).and_then(|ext| ext.to_str()).is_some_and(|subext| matches!(subext, "p" | "pre"));
+}
+
 pub fn update_path<'a>(path: &'a mut PathBuf, options: &'a Options) -> &'a PathBuf {
     let initial_path = path.clone();
 
+    // Declaration files are passed through as is
+    if is_declaration_path(path) {
+        return path;
+    }
+
     fn get_result_subext(options: &Options) -> Option<&str> {
         if optional!(#[cfg(feature = "minify")] options.minify).unwrap_or_default() {
             Some("min")
@@ -146,7 +189,7 @@ pub fn update_path<'a>(path: &'a mut PathBuf, options: &'a Options) -> &'a PathB
                     "mts" => "mjs",
 
                     #[cfg(any(feature = "compile", feature = "transpile"))]
-                    "tsx" if options.jsx_factory == None && options.use_jsx => "jsx",
+                    "tsx" if options.use_jsx && options.resolved_jsx_mode() == crate::JsxMode::Preserve => "jsx",
 
                     "ts" | _ => "js"
                 }
@@ -169,4 +212,24 @@ pub fn update_path<'a>(path: &'a mut PathBuf, options: &'a Options) -> &'a PathB
     }
     
     return path;
-}
\ No newline at end of file
+}
+
+// Updates an output path like '*.js' or '*.min.js' to the path of its declaration file like '*.d.ts'
+pub fn update_declaration_path<'a>(path: &'a mut PathBuf) -> &'a PathBuf {
+    if is_declaration_path(path) {
+        return path;
+    }
+
+    let ext = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("mjs") | Some("mts") => "d.mts",
+        Some("cjs") | Some("cts") => "d.cts",
+        _ => "d.ts"
+    };
+
+    if path.file_stem().and_then(|stem| Path::new(stem).extension()).and_then(|ext| ext.to_str()).map(|ext| matches!(ext, "p" | "min")).unwrap_or_default() {
+        path.set_extension("");
+    }
+
+    path.set_extension(ext);
+    return path;
+}
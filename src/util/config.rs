@@ -0,0 +1,146 @@
+// mtsc.toml Support
+// Top-level keys set default options, and each table in [overrides] sets options for files matching a glob pattern
+// Patterns are relative to the directory of the config file, and later overrides take precedence over earlier ones
+//
+//     minify = true
+//     macros = ["DEBUG=0"]
+//
+//     [overrides]
+//     "src/legacy/**" = { target = "es5" }
+//     "*.test.ts" = { minify = false, macros = ["DEBUG=1"] }
+use std::path::{Path,PathBuf};
+
+use super::glob::Glob;
+use crate::Options;
+use crate::error::{Error,Stage,Location};
+use crate::diagnostic::{self,Diagnostic,Severity};
+
+// Finds the nearest mtsc.toml in the directory of the given file or any of its parents
+pub fn find_config(path: &Path) -> Option<PathBuf> {
+    return super::find_nearest(path, "mtsc.toml");
+}
+
+// Tables are merged key by key so that overriding one field of a nested table like emit keeps the others
+fn merge(base: &mut toml::Table, overlay: &toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => merge(base, overlay),
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+fn location(filename: &Option<String>, text: &str, error: &toml::de::Error) -> Location {
+    return match error.span() {
+        Some(span) => {
+            let before = &text[..span.start];
+            let line = before.matches('\n').count() + 1;
+            let column = before.chars().rev().take_while(|c| *c != '\n').count() + 1;
+            Location::new(filename.clone(), Some(line), Some(column))
+        },
+        None => Location::file(filename.clone())
+    };
+}
+
+// Keys that are not options, usually typos or options of features this build does not have
+// Type errors are left to the final deserialization, which knows which override applies
+fn unknown_keys(table: &toml::Table) -> Vec<String> {
+    let mut keys = Vec::new();
+    let _: Result<Options, toml::de::Error> = serde_ignored::deserialize(toml::Value::Table(table.clone()), |path| keys.push(path.to_string()));
+    return keys;
+}
+
+// The path of a file relative to a directory, which is how override patterns are matched
+fn relative_path(directory: &Path, path: &Path) -> PathBuf {
+    let absolute = std::env::current_dir().map(|current_dir| current_dir.join(path)).unwrap_or_else(|_| path.to_path_buf());
+    let absolute = std::fs::canonicalize(&absolute).unwrap_or(absolute);
+    let directory = std::fs::canonicalize(directory).unwrap_or_else(|_| directory.to_path_buf());
+
+    return absolute.strip_prefix(&directory).map(Path::to_path_buf).unwrap_or(absolute);
+}
+
+// Reads options from an mtsc.toml into options, including any overrides that match path
+// Fields that are not set by the config keep their current values
+pub fn update_options_from_config<'a>(config_path: &Path, path: Option<&Path>, options: &'a mut Options) -> Result<&'a mut Options, Error> {
+    let filename = Some(config_path.to_string_lossy().into_owned());
+    let text = std::fs::read_to_string(config_path).map_err(|e| Error::io(filename.clone(), e))?;
+    let mut config: toml::Table = toml::from_str(&text).map_err(|e| Error::new(Stage::Config, location(&filename, &text, &e), e.message()))?;
+
+    let overrides = match config.remove("overrides") {
+        Some(toml::Value::Table(overrides)) => overrides,
+        Some(_) => return Err(Error::new(Stage::Config, Location::file(filename), "expected overrides to be a table")),
+        None => toml::Table::new()
+    };
+
+    let warning = |message: String| Diagnostic::new(Stage::Config, Severity::Warning, Location::file(filename.clone()), message);
+    let mut warnings: Vec<Diagnostic> = unknown_keys(&config).into_iter()
+        .map(|key| warning(format!("unknown option '{}' will be ignored", key)))
+        .collect();
+    for (pattern, value) in overrides.iter() {
+        if let toml::Value::Table(overlay) = value {
+            warnings.extend(unknown_keys(overlay).into_iter().map(|key| warning(format!("unknown option '{}' in the override for '{}' will be ignored", key, pattern))));
+        }
+    }
+    diagnostic::report(warnings, options.diagnostic_sink.as_ref(), false)?;
+
+    let mut table = match toml::Value::try_from(&*options) {
+        Ok(toml::Value::Table(table)) => table,
+        _ => return Err(Error::new(Stage::Config, Location::file(filename), "failed to merge options"))
+    };
+    merge(&mut table, &config);
+
+    if let Some(path) = path {
+        let path = relative_path(config_path.parent().unwrap_or(Path::new("")), path);
+
+        for (pattern, value) in overrides.iter() {
+            match value {
+                toml::Value::Table(overlay) if Glob::new(pattern).matches(&path) => merge(&mut table, overlay),
+                toml::Value::Table(_) => {},
+                _ => return Err(Error::new(Stage::Config, Location::file(filename), format!("expected the override for '{}' to be a table", pattern)))
+            }
+        }
+    }
+
+    let mut merged: Options = toml::Value::Table(table).try_into().map_err(|e: toml::de::Error| Error::new(Stage::Config, Location::file(filename.clone()), e.message()))?;
+
+    // Neither of these belong in a config
+    merged.filename = options.filename.take();
+    merged.diagnostic_sink = options.diagnostic_sink.take();
+
+    *options = merged;
+    return Ok(options);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc,Mutex};
+    use crate::diagnostic::{Action,DiagnosticSink};
+
+    #[test]
+    fn unknown_keys_are_reported() {
+        let config_path = std::env::temp_dir().join(format!("mtsc-test-{}.toml", std::process::id()));
+        std::fs::write(&config_path, "minfy = true\n\n[overrides]\n\"*.ts\" = { sourcemap = \"inline\" }\n").unwrap();
+
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let sink_messages = messages.clone();
+        let mut options = Options {
+            diagnostic_sink: Some(DiagnosticSink::new(move |diagnostic| {
+                sink_messages.lock().unwrap().push(diagnostic.message.clone());
+                return Action::Continue;
+            })),
+            ..Default::default()
+        };
+
+        let result = update_options_from_config(&config_path, Some(Path::new("input.ts")), &mut options).map(|_| ());
+        std::fs::remove_file(&config_path).unwrap();
+        result.unwrap();
+
+        assert_eq!(*messages.lock().unwrap(), vec![
+            String::from("unknown option 'minfy' will be ignored"),
+            String::from("unknown option 'sourcemap' in the override for '*.ts' will be ignored"),
+        ]);
+    }
+}
@@ -0,0 +1,271 @@
+// Glob Patterns
+// Supports '*', '**', '?', character classes like '[a-z]' or '[!a]', and alternatives like '{ts,tsx}'
+use std::io;
+use std::path::{Path,PathBuf};
+
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Glob {
+    pattern: String,
+    // Each alternative with braces expanded
+    alternatives: Vec<Vec<char>>,
+}
+
+pub fn is_glob(s: &str) -> bool {
+    return s.contains(|c: char| matches!(c, '*' | '?' | '[' | '{'));
+}
+
+// Joins components with '/' so that patterns work the same on every platform
+fn normalize(path: &Path) -> String {
+    return path.components()
+        .filter(|component| !matches!(component, std::path::Component::CurDir))
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+        .replacen("//", "/", 1);
+}
+
+fn expand_braces(pattern: &str) -> Vec<String> {
+    let Some(open) = pattern.find('{') else {
+        return vec![String::from(pattern)];
+    };
+
+    // Find the matching brace and the top-level commas within it
+    let mut depth = 0;
+    let mut splits = vec![open];
+    let mut close = None;
+    for (i, c) in pattern.char_indices().skip_while(|(i, _)| *i <= open) {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => {
+                close = Some(i);
+                break;
+            },
+            '}' => depth -= 1,
+            ',' if depth == 0 => splits.push(i),
+            _ => {}
+        }
+    }
+
+    let Some(close) = close else {
+        return vec![String::from(pattern)];
+    };
+    splits.push(close);
+
+    let (prefix, suffix) = (&pattern[..open], &pattern[close+1..]);
+    return splits.windows(2)
+        .flat_map(|window| expand_braces(&format!("{}{}{}", prefix, &pattern[window[0]+1..window[1]], suffix)))
+        .collect();
+}
+
+fn match_class(pattern: &[char], c: char) -> Option<(bool, usize)> {
+    let negated = matches!(pattern.get(1), Some('!') | Some('^'));
+    let start = if negated {2} else {1};
+    let end = start + pattern[start..].iter().skip(1).position(|c| *c == ']')? + 1;
+
+    let class = &pattern[start..end];
+    let mut matched = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i+1] == '-' {
+            matched |= class[i] <= c && c <= class[i+2];
+            i += 3;
+        } else {
+            matched |= class[i] == c;
+            i += 1;
+        }
+    }
+
+    return Some((matched != negated, end + 1));
+}
+
+fn match_here(pattern: &[char], s: &[char]) -> bool {
+    match pattern.first() {
+        None => s.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            // '**/' matches any number of whole directories while a trailing '**' matches everything
+            let (rest, whole_components) = match pattern.get(2) {
+                Some('/') => (&pattern[3..], true),
+                _ => (&pattern[2..], false)
+            };
+            (0..=s.len()).any(|i| (!whole_components || i == 0 || s[i-1] == '/') && match_here(rest, &s[i..]))
+        },
+        Some('*') => {
+            for i in 0..=s.len() {
+                if match_here(&pattern[1..], &s[i..]) {
+                    return true;
+                }
+                if i < s.len() && s[i] == '/' {
+                    break;
+                }
+            }
+            false
+        },
+        Some('?') => !s.is_empty() && s[0] != '/' && match_here(&pattern[1..], &s[1..]),
+        Some('[') if !s.is_empty() && s[0] != '/' => match match_class(pattern, s[0]) {
+            Some((matched, length)) => matched && match_here(&pattern[length..], &s[1..]),
+            None => s[0] == '[' && match_here(&pattern[1..], &s[1..])
+        },
+        Some(c) => s.first() == Some(c) && match_here(&pattern[1..], &s[1..])
+    }
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Self {
+        let pattern = normalize(Path::new(pattern));
+        let alternatives = expand_braces(&pattern).into_iter().map(|alternative| alternative.chars().collect()).collect();
+        return Glob { pattern, alternatives };
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    // Patterns without a '/' match the file name in any directory, like in .gitignore files
+    pub fn matches(&self, path: &Path) -> bool {
+        return self.is_match(path, true);
+    }
+
+    fn is_match(&self, path: &Path, match_names: bool) -> bool {
+        let path: Vec<char> = normalize(path).chars().collect();
+        let name_start = path.iter().rposition(|c| *c == '/').map_or(0, |i| i + 1);
+
+        return self.alternatives.iter().any(|pattern| {
+            if match_names && !pattern.contains(&'/') {
+                match_here(pattern, &path[name_start..])
+            } else {
+                match_here(pattern, &path)
+            }
+        });
+    }
+
+    // The leading directories without any special characters, where searching for matches starts
+    pub fn base(&self) -> PathBuf {
+        let mut base = PathBuf::new();
+        let components: Vec<&str> = self.pattern.split('/').collect();
+
+        for component in &components[..components.len() - 1] {
+            if is_glob(component) {
+                break;
+            }
+            base.push(if component.is_empty() {"/"} else {component});
+        }
+
+        return if base.as_os_str().is_empty() {PathBuf::from(".")} else {base};
+    }
+}
+
+// Lists every file under a directory in a stable order, skipping hidden entries and node_modules
+pub fn walk(directory: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut entries = std::fs::read_dir(directory)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let name = entry.file_name();
+        if name.to_string_lossy().starts_with('.') || name == "node_modules" {
+            continue;
+        }
+
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            files.extend(walk(&path)?);
+        } else {
+            files.push(path);
+        }
+    }
+
+    return Ok(files);
+}
+
+// Finds every file matching a pattern relative to the current directory
+pub fn glob(pattern: &str) -> io::Result<Vec<PathBuf>> {
+    let glob = Glob::new(pattern);
+    let base = glob.base();
+
+    if !base.is_dir() {
+        return Ok(vec![]);
+    }
+
+    return Ok(walk(&base)?.into_iter().filter(|path| glob.is_match(path, false)).collect());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        return Glob::new(pattern).matches(Path::new(path));
+    }
+
+    #[test]
+    fn double_star() {
+        assert!(matches("**/*.ts", "a.ts"));
+        assert!(matches("**/*.ts", "src/a/b.ts"));
+        assert!(!matches("**/*.ts", "src/a.tsx"));
+        assert!(matches("src/**/test/*.ts", "src/test/a.ts"));
+        assert!(matches("src/**/test/*.ts", "src/a/b/test/a.ts"));
+        assert!(!matches("src/**/test/*.ts", "src/latest/a.ts"));
+        assert!(matches("src/**", "src/a/b.ts"));
+        assert!(!matches("src/**", "lib/a.ts"));
+    }
+
+    #[test]
+    fn single_star() {
+        assert!(matches("src/*.ts", "src/a.ts"));
+        assert!(!matches("src/*.ts", "src/a/b.ts"));
+        // Without a '/', the pattern matches the file name in any directory
+        assert!(matches("*.test.ts", "src/a/b.test.ts"));
+        assert!(!matches("*.test.ts", "src/a/b.ts"));
+    }
+
+    #[test]
+    fn question_mark() {
+        assert!(matches("a?.ts", "ab.ts"));
+        assert!(!matches("a?.ts", "a.ts"));
+        assert!(!matches("a?.ts", "abc.ts"));
+        assert!(!matches("src?a.ts", "src/a.ts"));
+    }
+
+    #[test]
+    fn character_classes() {
+        assert!(matches("[a-c].ts", "b.ts"));
+        assert!(!matches("[a-c].ts", "d.ts"));
+        assert!(matches("[xyz].ts", "y.ts"));
+        assert!(matches("[!a].ts", "b.ts"));
+        assert!(!matches("[!a].ts", "a.ts"));
+        assert!(!matches("[^a].ts", "a.ts"));
+        assert!(matches("[]].ts", "].ts"));
+        assert!(!matches("src[/]a.ts", "src/a.ts"));
+        // An unterminated class is a literal '['
+        assert!(matches("[a.ts", "[a.ts"));
+    }
+
+    #[test]
+    fn alternatives() {
+        assert!(matches("*.{ts,tsx}", "a.ts"));
+        assert!(matches("*.{ts,tsx}", "a.tsx"));
+        assert!(!matches("*.{ts,tsx}", "a.js"));
+        assert!(matches("{src,lib/{a,b}}/*.ts", "lib/b/c.ts"));
+    }
+
+    #[test]
+    fn path_separators() {
+        assert!(matches("./src/*.ts", "src/a.ts"));
+        assert!(matches("src/*.ts", "./src/a.ts"));
+        assert!(matches("src//*.ts", "src/a.ts"));
+        #[cfg(windows)]
+        {
+            assert!(matches("src/*.ts", "src\\a.ts"));
+            assert!(matches("src\\**\\*.ts", "src/a/b.ts"));
+        }
+    }
+
+    #[test]
+    fn base() {
+        assert_eq!(Glob::new("src/lib/**/*.ts").base(), PathBuf::from("src/lib"));
+        assert_eq!(Glob::new("src/*/index.ts").base(), PathBuf::from("src"));
+        assert_eq!(Glob::new("*.ts").base(), PathBuf::from("."));
+        #[cfg(unix)]
+        assert_eq!(Glob::new("/src/*.ts").base(), PathBuf::from("/src"));
+    }
+}
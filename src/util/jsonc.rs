@@ -0,0 +1,326 @@
+// JSON with Comments
+// A small parser for the JSON dialect used by tsconfig.json: comments and trailing commas are allowed
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[derive(Clone,Debug,PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<Member>),
+}
+
+// Object members keep their order and one-based position so that messages can point at the key
+#[derive(Clone,Debug,PartialEq)]
+pub struct Member {
+    pub key: String,
+    pub value: Value,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Value {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members.iter().rev().find(|member| member.key == key).map(|member| &member.value),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Value>> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&Vec<Member>> {
+        match self {
+            Value::Object(members) => Some(members),
+            _ => None
+        }
+    }
+}
+
+// A parse error with a one-based position
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct ParseError {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T, S: Into<String>>(&self, message: S) -> Result<T, ParseError> {
+        return Err(ParseError { message: message.into(), line: self.line, column: self.column });
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        return Some(c);
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ParseError> {
+        match self.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => self.error(format!("expected '{}' but found '{}'", expected, c)),
+            None => self.error(format!("expected '{}' but found end of file", expected))
+        }
+    }
+
+    // Skips whitespace and comments
+    fn skip_trivia(&mut self) -> Result<(), ParseError> {
+        loop {
+            match self.chars.peek() {
+                Some(c) if c.is_whitespace() || *c == '\u{FEFF}' => {
+                    self.next();
+                },
+                Some('/') => {
+                    self.next();
+                    match self.next() {
+                        Some('/') => while !matches!(self.next(), Some('\n') | None) {},
+                        Some('*') => loop {
+                            match self.next() {
+                                Some('*') if self.chars.peek() == Some(&'/') => {
+                                    self.next();
+                                    break;
+                                },
+                                Some(_) => {},
+                                None => return self.error("unterminated comment")
+                            }
+                        },
+                        _ => return self.error("unexpected '/'")
+                    }
+                },
+                _ => return Ok(())
+            }
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, ParseError> {
+        self.skip_trivia()?;
+        return match self.chars.peek().copied() {
+            Some('{') => self.parse_object(),
+            Some('[') => self.parse_array(),
+            Some('"') => self.parse_string().map(Value::String),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.parse_number(),
+            Some(c) if c.is_ascii_alphabetic() => {
+                let mut word = String::new();
+                while let Some(c) = self.chars.peek().copied().filter(|c| c.is_ascii_alphanumeric()) {
+                    word.push(c);
+                    self.next();
+                }
+                match word.as_str() {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    "null" => Ok(Value::Null),
+                    _ => self.error(format!("unexpected '{}'", word))
+                }
+            },
+            Some(c) => self.error(format!("unexpected '{}'", c)),
+            None => self.error("unexpected end of file")
+        };
+    }
+
+    fn parse_object(&mut self) -> Result<Value, ParseError> {
+        self.expect('{')?;
+        let mut members = Vec::new();
+
+        loop {
+            self.skip_trivia()?;
+            if self.chars.peek() == Some(&'}') {
+                self.next();
+                return Ok(Value::Object(members));
+            }
+
+            let (line, column) = (self.line, self.column);
+            if self.chars.peek() != Some(&'"') {
+                return self.error("expected a property name");
+            }
+            let key = self.parse_string()?;
+
+            self.skip_trivia()?;
+            self.expect(':')?;
+            let value = self.parse_value()?;
+            members.push(Member { key, value, line, column });
+
+            self.skip_trivia()?;
+            match self.next() {
+                Some(',') => {},
+                Some('}') => return Ok(Value::Object(members)),
+                _ => return self.error("expected ',' or '}'")
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Value, ParseError> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+
+        loop {
+            self.skip_trivia()?;
+            if self.chars.peek() == Some(&']') {
+                self.next();
+                return Ok(Value::Array(values));
+            }
+
+            values.push(self.parse_value()?);
+
+            self.skip_trivia()?;
+            match self.next() {
+                Some(',') => {},
+                Some(']') => return Ok(Value::Array(values)),
+                _ => return self.error("expected ',' or ']'")
+            }
+        }
+    }
+
+    fn parse_hex_escape(&mut self) -> Result<u32, ParseError> {
+        let mut code = 0;
+        for _ in 0..4 {
+            match self.next().and_then(|c| c.to_digit(16)) {
+                Some(digit) => code = code * 16 + digit,
+                None => return self.error("invalid unicode escape")
+            }
+        }
+        return Ok(code);
+    }
+
+    fn parse_string(&mut self) -> Result<String, ParseError> {
+        self.expect('"')?;
+        let mut s = String::new();
+
+        loop {
+            match self.next() {
+                Some('"') => return Ok(s),
+                Some('\\') => match self.next() {
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some('/') => s.push('/'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some('n') => s.push('\n'),
+                    Some('r') => s.push('\r'),
+                    Some('t') => s.push('\t'),
+                    Some('u') => {
+                        let mut code = self.parse_hex_escape()?;
+
+                        // Surrogate pairs are written as two escapes
+                        if (0xD800..0xDC00).contains(&code) && self.chars.peek() == Some(&'\\') {
+                            self.next();
+                            self.expect('u')?;
+                            let low = self.parse_hex_escape()?;
+                            code = 0x10000 + ((code - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF);
+                        }
+
+                        s.push(char::from_u32(code).unwrap_or('\u{FFFD}'));
+                    },
+                    _ => return self.error("invalid escape sequence")
+                },
+                Some('\n') | None => return self.error("unterminated string"),
+                Some(c) => s.push(c)
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Value, ParseError> {
+        let mut text = String::new();
+        while let Some(c) = self.chars.peek().copied().filter(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')) {
+            text.push(c);
+            self.next();
+        }
+
+        return match text.parse::<f64>() {
+            Ok(number) => Ok(Value::Number(number)),
+            Err(_) => self.error(format!("invalid number '{}'", text))
+        };
+    }
+}
+
+pub fn parse(text: &str) -> Result<Value, ParseError> {
+    let mut parser = Parser { chars: text.chars().peekable(), line: 1, column: 1 };
+    let value = parser.parse_value()?;
+
+    parser.skip_trivia()?;
+    if parser.chars.peek().is_some() {
+        return parser.error("unexpected content after the end of the value");
+    }
+
+    return Ok(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments() {
+        let value = parse("// tsconfig\n{\n    /* block\n       comment */\n    \"a\": 1, // trailing\n    \"b\": /* inline */ true\n}\n// end").unwrap();
+        assert_eq!(value.get("a"), Some(&Value::Number(1.0)));
+        assert_eq!(value.get("b"), Some(&Value::Bool(true)));
+
+        let members = value.as_object().unwrap();
+        assert_eq!((members[0].line, members[0].column), (5, 5));
+        assert_eq!((members[1].line, members[1].column), (6, 5));
+    }
+
+    #[test]
+    fn trailing_commas() {
+        let value = parse("{\"a\": [1, 2,], \"b\": {\"c\": null,},}").unwrap();
+        assert_eq!(value.get("a"), Some(&Value::Array(vec![Value::Number(1.0), Value::Number(2.0)])));
+        assert_eq!(value.get("b").and_then(|b| b.get("c")), Some(&Value::Null));
+
+        assert!(parse("[1,,]").is_err());
+        assert!(parse("{,}").is_err());
+    }
+
+    #[test]
+    fn comment_markers_in_strings() {
+        let value = parse("{\"url\": \"http://example.com\", \"include\": [\"src/**/*\", \"/*.ts\"], \"a//b\": \"*/\"}").unwrap();
+        assert_eq!(value.get("url").and_then(Value::as_str), Some("http://example.com"));
+        assert_eq!(value.get("include"), Some(&Value::Array(vec![Value::String(String::from("src/**/*")), Value::String(String::from("/*.ts"))])));
+        assert_eq!(value.get("a//b").and_then(Value::as_str), Some("*/"));
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(parse(r#""a\"\\\/\n\u00e9\ud83d\ude00""#), Ok(Value::String(String::from("a\"\\/\né\u{1F600}"))));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse("{\n  \"a\": 1 /* unterminated"), Err(ParseError { message: String::from("unterminated comment"), line: 2, column: 25 }));
+        assert_eq!(parse("{\"a\": tru}").map_err(|e| e.message), Err(String::from("unexpected 'tru'")));
+        assert_eq!(parse("{} {}").map_err(|e| e.message), Err(String::from("unexpected content after the end of the value")));
+        assert_eq!(parse("{a: 1}").map_err(|e| e.message), Err(String::from("expected a property name")));
+    }
+}
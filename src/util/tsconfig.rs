@@ -0,0 +1,230 @@
+// tsconfig.json Support
+use std::path::{Path,PathBuf};
+
+use super::jsonc::{self,Value,Member};
+use crate::Options;
+use crate::error::{Error,Stage,Location};
+use crate::diagnostic::{self,Diagnostic,Severity};
+
+// Options that only affect how a project is laid out or resolved, which do not matter when compiling a single file
+const IGNORED_OPTIONS: &[&str] = &[
+    "outDir", "rootDir", "rootDirs", "baseUrl", "paths", "composite", "incremental", "tsBuildInfoFile",
+    "moduleResolution", "moduleSuffixes", "resolveJsonModule", "resolvePackageJsonExports", "resolvePackageJsonImports", "customConditions",
+    "allowImportingTsExtensions", "allowArbitraryExtensions", "allowJs", "checkJs", "maxNodeModuleJsDepth", "types", "typeRoots", "lib", "noLib",
+    "declarationDir", "skipLibCheck", "skipDefaultLibCheck", "forceConsistentCasingInFileNames", "allowSyntheticDefaultImports",
+    "isolatedModules", "isolatedDeclarations", "allowUmdGlobalAccess", "noResolve", "preserveSymlinks", "disableSizeLimit",
+    "disableSourceOfProjectReferenceRedirect", "disableSolutionSearching", "disableReferencedProjectLoad", "plugins",
+    "listFiles", "listEmittedFiles", "traceResolution", "extendedDiagnostics", "diagnostics", "generateCpuProfile", "explainFiles",
+    "pretty", "noErrorTruncation", "preserveWatchOutput", "assumeChangesOnlyAffectDirectDependencies",
+];
+
+// Options that only affect type checking, which are kept in Options::type_check_options and have no effect unless type checking
+const CHECKING_OPTIONS: &[&str] = &[
+    "strict", "noImplicitAny", "strictNullChecks", "strictFunctionTypes", "strictBindCallApply", "strictPropertyInitialization",
+    "noImplicitThis", "useUnknownInCatchVariables", "noUnusedLocals", "noUnusedParameters", "noImplicitReturns",
+    "noFallthroughCasesInSwitch", "noUncheckedIndexedAccess", "noImplicitOverride", "noPropertyAccessFromIndexSignature",
+    "exactOptionalPropertyTypes", "allowUnreachableCode", "allowUnusedLabels", "suppressExcessPropertyErrors",
+];
+
+// Options deprecated in TypeScript 5.0 and removed in 5.5, which TypeScript would reject
+const REMOVED_OPTIONS: &[&str] = &[
+    "noStrictGenericChecks", "suppressImplicitAnyIndexErrors", "keyofStringsOnly", "preserveValueImports",
+    "importsNotUsedAsValues", "out", "charset", "noImplicitUseStrict", "prepend",
+];
+
+// Finds the nearest tsconfig.json in the directory of the given file or any of its parents
+pub fn find_tsconfig(path: &Path) -> Option<PathBuf> {
+    return super::find_nearest(path, "tsconfig.json");
+}
+
+// Finds the file an extends entry refers to; packages are looked up in node_modules like TypeScript does
+fn resolve_extends(directory: &Path, name: &str) -> Option<PathBuf> {
+    fn candidates(path: PathBuf) -> [PathBuf; 3] {
+        let mut with_json = path.clone().into_os_string();
+        with_json.push(".json");
+        return [path.clone(), PathBuf::from(with_json), path.join("tsconfig.json")];
+    }
+
+    if name.starts_with('.') || Path::new(name).is_absolute() {
+        return candidates(directory.join(name)).into_iter().find(|path| path.is_file());
+    }
+
+    return directory.ancestors()
+        .flat_map(|directory| candidates(directory.join("node_modules").join(name)))
+        .find(|path| path.is_file());
+}
+
+// Reads the compiler options of a tsconfig.json and everything it extends, with later entries overriding earlier ones
+fn load(path: &Path, visited: &mut Vec<PathBuf>, compiler_options: &mut Vec<(PathBuf, Member)>) -> Result<(), Error> {
+    let filename = Some(path.to_string_lossy().into_owned());
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+    if visited.contains(&path) {
+        return Err(Error::new(Stage::Config, Location::file(filename), "circularity detected while resolving extends"));
+    }
+    visited.push(path.clone());
+
+    let text = std::fs::read_to_string(&path).map_err(|e| Error::io(filename.clone(), e))?;
+    let config = jsonc::parse(&text).map_err(|e| Error::new(Stage::Config, Location::new(filename.clone(), Some(e.line), Some(e.column)), e.message))?;
+
+    if config.as_object().is_none() {
+        return Err(Error::new(Stage::Config, Location::file(filename), "expected an object"));
+    }
+
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let extends: Vec<&str> = match config.get("extends") {
+        Some(Value::String(name)) => vec![name],
+        Some(Value::Array(names)) => names.iter().filter_map(Value::as_str).collect(),
+        _ => vec![]
+    };
+
+    for name in extends {
+        match resolve_extends(directory, name) {
+            Some(base) => load(&base, visited, compiler_options)?,
+            None => return Err(Error::new(Stage::Config, Location::file(filename), format!("cannot find base config '{}'", name)))
+        }
+    }
+
+    if let Some(members) = config.get("compilerOptions").and_then(Value::as_object) {
+        for member in members {
+            compiler_options.retain(|(_, existing)| existing.key != member.key);
+            compiler_options.push((path.clone(), member.clone()));
+        }
+    }
+
+    visited.pop();
+    return Ok(());
+}
+
+// Maps a single compiler option onto options; an error is reported as a warning and the option is skipped
+fn apply(key: &str, value: &Value, options: &mut Options) -> Result<(), String> {
+    #[allow(unused_macros)]
+    macro_rules! boolean {
+        () => {
+            value.as_bool().ok_or_else(|| format!("expected a boolean for compiler option '{}'", key))?
+        };
+    }
+
+    #[allow(unused_macros)]
+    macro_rules! string {
+        () => {
+            value.as_str().ok_or_else(|| format!("expected a string for compiler option '{}'", key))?
+        };
+    }
+
+    match key {
+        #[cfg(feature = "common")]
+        "target" => options.target = string!().to_ascii_lowercase(),
+        #[cfg(feature = "common")]
+        "module" => options.module_kind = string!().parse()?,
+        #[cfg(feature = "common")]
+        "moduleDetection" => options.module |= string!().eq_ignore_ascii_case("force"),
+
+        // See update_jsx
+        #[cfg(any(feature = "transpile", feature = "compile"))]
+        "jsx" | "jsxFactory" | "jsxFragmentFactory" | "jsxImportSource" => {},
+
+        "sourceMap" => if boolean!() {
+            if options.source_map == crate::SourceMapMode::None {
+                options.source_map = crate::SourceMapMode::External;
+            }
+        } else if options.source_map == crate::SourceMapMode::External {
+            options.source_map = crate::SourceMapMode::None;
+        },
+        "inlineSourceMap" => if boolean!() {
+            options.source_map = crate::SourceMapMode::Inline;
+        } else if options.source_map == crate::SourceMapMode::Inline {
+            options.source_map = crate::SourceMapMode::None;
+        },
+
+        #[cfg(any(feature = "transpile", feature = "compile"))]
+        "declaration" => options.declaration = boolean!(),
+        #[cfg(any(feature = "transpile", feature = "compile"))]
+        "noEmitOnError" => options.fail_on_error = boolean!(),
+
+        #[cfg(any(feature = "transpile", feature = "compile"))]
+        "experimentalDecorators" => options.emit.experimental_decorators = boolean!(),
+        #[cfg(any(feature = "transpile", feature = "compile"))]
+        "emitDecoratorMetadata" => options.emit.emit_decorator_metadata = boolean!(),
+        #[cfg(any(feature = "transpile", feature = "compile"))]
+        "useDefineForClassFields" => options.emit.use_define_for_class_fields = Some(boolean!()),
+        #[cfg(any(feature = "transpile", feature = "compile"))]
+        "importHelpers" => options.emit.import_helpers = boolean!(),
+        #[cfg(any(feature = "transpile", feature = "compile"))]
+        "esModuleInterop" => options.emit.es_module_interop = boolean!(),
+        #[cfg(any(feature = "transpile", feature = "compile"))]
+        "downlevelIteration" => options.emit.downlevel_iteration = boolean!(),
+        #[cfg(any(feature = "transpile", feature = "compile"))]
+        "verbatimModuleSyntax" => options.emit.verbatim_module_syntax = boolean!(),
+        #[cfg(any(feature = "transpile", feature = "compile"))]
+        "removeComments" => options.emit.remove_comments = boolean!(),
+
+        #[cfg(feature = "compile")]
+        _ if CHECKING_OPTIONS.contains(&key) => {
+            options.type_check_options.insert(String::from(key), boolean!());
+        },
+        // Without the compile feature nothing is type checked
+        #[cfg(not(feature = "compile"))]
+        _ if CHECKING_OPTIONS.contains(&key) => {},
+
+        _ if IGNORED_OPTIONS.contains(&key) => {},
+        _ if REMOVED_OPTIONS.contains(&key) => return Err(format!("compiler option '{}' was removed in TypeScript 5.5 and will be ignored", key)),
+        _ => return Err(format!("compiler option '{}' is not supported and will be ignored", key))
+    }
+
+    return Ok(());
+}
+
+// The jsx options are only meaningful together, so they are mapped at once
+#[cfg(any(feature = "transpile", feature = "compile"))]
+fn update_jsx(compiler_options: &[(PathBuf, Member)], options: &mut Options) -> Result<(), (usize, String)> {
+    let get = |key: &str| compiler_options.iter().position(|(_, member)| member.key == key);
+    let get_string = |key: &str| -> Result<Option<String>, (usize, String)> {
+        return match get(key) {
+            Some(i) => compiler_options[i].1.value.as_str()
+                .map(|value| Some(String::from(value)))
+                .ok_or_else(|| (i, format!("expected a string for compiler option '{}'", key))),
+            None => Ok(None)
+        };
+    };
+
+    let jsx_factory = get_string("jsxFactory")?;
+    let jsx_fragment = get_string("jsxFragmentFactory")?;
+    let jsx_import_source = get_string("jsxImportSource")?;
+
+    if let Some(i) = get("jsx") {
+        options.jsx_mode = Some(get_string("jsx")?.unwrap_or_default().parse().map_err(|message| (i, message))?);
+        options.use_jsx = true;
+        options.jsx_factory = jsx_factory;
+        options.jsx_fragment = jsx_fragment;
+        options.jsx_import_source = jsx_import_source;
+    }
+
+    return Ok(());
+}
+
+// Reads compiler options from a tsconfig.json (following extends) into options
+// Unsupported options and invalid values are reported as warnings to options.diagnostic_sink
+pub fn update_options_from_tsconfig<'a>(path: &Path, options: &'a mut Options) -> Result<&'a mut Options, Error> {
+    let mut compiler_options = Vec::new();
+    load(path, &mut vec![], &mut compiler_options)?;
+
+    let mut warnings = Vec::new();
+    let mut warn = |(path, member): &(PathBuf, Member), message: String| {
+        warnings.push(Diagnostic::new(Stage::Config, Severity::Warning, Location::new(Some(path.to_string_lossy().into_owned()), Some(member.line), Some(member.column)), message));
+    };
+
+    #[cfg(any(feature = "transpile", feature = "compile"))]
+    if let Err((i, message)) = update_jsx(&compiler_options, options) {
+        warn(&compiler_options[i], message);
+    }
+
+    for compiler_option in compiler_options.iter() {
+        if let Err(message) = apply(&compiler_option.1.key, &compiler_option.1.value, options) {
+            warn(compiler_option, message);
+        }
+    }
+
+    diagnostic::report(warnings, options.diagnostic_sink.as_ref(), false)?;
+    return Ok(options);
+}